@@ -0,0 +1,156 @@
+// Compares the streaming parser against the quickxml_to_serde JSON round-trip it replaced, on
+// generated feeds large enough for the difference to show. The old pipeline is measured only up
+// to the serde_json::Value it built, so its numbers are a lower bound. A separate target so the
+// counting allocator below only ever sees the benchmark.
+//
+// cargo bench --bench parse
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  sync::atomic::{AtomicUsize, Ordering},
+  time::{Duration, Instant},
+};
+
+use quickxml_to_serde::{xml_string_to_json, Config};
+use rss_reader_service::service::{parse_feed_xml, ParsedFeed};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let ptr = System.alloc(layout);
+    if !ptr.is_null() {
+      let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+      PEAK.fetch_max(allocated, Ordering::Relaxed);
+    }
+    ptr
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout);
+    ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    let new_ptr = System.realloc(ptr, layout, new_size);
+    if !new_ptr.is_null() {
+      let allocated = ALLOCATED.fetch_add(new_size, Ordering::Relaxed) + new_size;
+      PEAK.fetch_max(allocated, Ordering::Relaxed);
+      ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+    new_ptr
+  }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: u32 = 5;
+const SIZES: &[usize] = &[1_000, 10_000];
+
+const DESCRIPTION: &str = "&lt;p&gt;This week we look at &lt;a href=&quot;https://example.org&quot;&gt;a terrible \
+  video game&lt;/a&gt;, the scaling laws behind it and why nobody should ever ship it.&lt;/p&gt;";
+
+fn large_rss(items: usize) -> String {
+  let items: String = (0..items).map(|i| format!(
+    "<item>
+      <title>Entry {i}</title>
+      <link>https://example.org/posts/{i}</link>
+      <guid isPermaLink=\"false\">post-{i}</guid>
+      <pubDate>Wed, 09 Oct 2024 18:55:25 GMT</pubDate>
+      <dc:creator>Pepper</dc:creator>
+      <description>{DESCRIPTION}</description>
+    </item>"
+  )).collect();
+
+  format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+    <rss version=\"2.0\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+      <channel><title>Large RSS</title><link>https://example.org/</link>{items}</channel>
+    </rss>")
+}
+
+fn large_atom(entries: usize) -> String {
+  let entries: String = (0..entries).map(|i| format!(
+    "<entry>
+      <id>urn:example:post-{i}</id>
+      <title type=\"html\">Entry {i}</title>
+      <link rel=\"alternate\" type=\"text/html\" href=\"https://example.org/posts/{i}\" />
+      <link rel=\"replies\" type=\"application/atom+xml\" href=\"https://example.org/posts/{i}/comments\" />
+      <published>2024-10-09T18:55:25Z</published>
+      <updated>2024-10-09T18:55:25Z</updated>
+      <author><name>Pepper</name></author>
+      <summary type=\"html\">{DESCRIPTION}</summary>
+    </entry>"
+  )).collect();
+
+  format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>
+    <feed xmlns=\"http://www.w3.org/2005/Atom\"><title>Large Atom</title>{entries}</feed>")
+}
+
+fn large_rdf(items: usize) -> String {
+  let items: String = (0..items).map(|i| format!(
+    "<item rdf:about=\"https://example.org/posts/{i}\">
+      <title>Entry {i}</title>
+      <link>https://example.org/posts/{i}</link>
+      <dc:date>2024-10-09T18:55:25Z</dc:date>
+      <description>{DESCRIPTION}</description>
+    </item>"
+  )).collect();
+
+  format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+    <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\" xmlns=\"http://purl.org/rss/1.0/\"
+      xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+      <channel rdf:about=\"https://example.org/\"><title>Large RDF</title></channel>{items}
+    </rdf:RDF>")
+}
+
+struct Measurement {
+  time: Duration,
+  peak_bytes: usize,
+}
+
+// Mean time and highest allocation above what was live before each run
+fn measure<T>(mut run: impl FnMut() -> T) -> Measurement {
+  let mut time = Duration::ZERO;
+  let mut peak_bytes = 0;
+
+  for _ in 0..ITERATIONS {
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let started = Instant::now();
+    let result = run();
+    time += started.elapsed();
+    peak_bytes = peak_bytes.max(PEAK.load(Ordering::Relaxed).saturating_sub(baseline));
+    drop(result);
+  }
+
+  Measurement { time: time / ITERATIONS, peak_bytes }
+}
+
+fn report(name: &str, xml: &str, expected_entries: usize) {
+  let streaming = measure(|| parse_feed_xml(xml, None, Some("https://example.org/feed.xml")).unwrap());
+  let parsed: ParsedFeed = parse_feed_xml(xml, None, Some("https://example.org/feed.xml")).unwrap();
+  assert_eq!(parsed.entries.len(), expected_entries);
+
+  println!("{name}: {:.1} MiB document", xml.len() as f64 / (1024.0 * 1024.0));
+  println!("  streaming parser   {:>10.2?} {:>10} KiB peak", streaming.time, streaming.peak_bytes / 1024);
+
+  let round_trip = measure(|| xml_string_to_json(xml.to_string(), &Config::new_with_defaults()).unwrap());
+  println!("  json round-trip    {:>10.2?} {:>10} KiB peak", round_trip.time, round_trip.peak_bytes / 1024);
+}
+
+fn main() {
+  for size in SIZES {
+    report(&format!("RSS, {size} items"), &large_rss(*size), *size);
+  }
+  for size in SIZES {
+    report(&format!("Atom, {size} entries"), &large_atom(*size), *size);
+  }
+  for size in SIZES {
+    report(&format!("RDF, {size} items"), &large_rdf(*size), *size);
+  }
+}
@@ -0,0 +1,4 @@
+-- Store HTTP validators so stale feeds can be revalidated with a conditional GET
+ALTER TABLE cache
+ADD COLUMN etag varchar,
+ADD COLUMN last_modified varchar;
@@ -0,0 +1,12 @@
+-- Persist parsed feed entries so history outlives the cached XML document
+CREATE TABLE IF NOT EXISTS entries (
+  id serial PRIMARY KEY,
+  feed_id int NOT NULL REFERENCES feeds(id) ON DELETE CASCADE,
+  identity varchar NOT NULL,
+  title varchar NOT NULL,
+  url varchar NOT NULL,
+  created_date timestamptz NOT NULL,
+  UNIQUE (feed_id, identity)
+);
+
+CREATE INDEX IF NOT EXISTS entries_feed_id_created_date_idx ON entries (feed_id, created_date DESC);
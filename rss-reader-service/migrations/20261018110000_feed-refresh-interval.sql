@@ -0,0 +1,6 @@
+-- Per-feed polling schedule, intervals are stored in seconds
+ALTER TABLE feeds
+ADD COLUMN refresh_interval int,
+ADD COLUMN refresh_hint int,
+ADD COLUMN unchanged_fetches int NOT NULL DEFAULT 0,
+ADD COLUMN next_fetch_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
@@ -0,0 +1,18 @@
+-- Record every fetch attempt and track feed health
+CREATE TABLE IF NOT EXISTS feed_fetch_log (
+  id serial PRIMARY KEY,
+  feed_id int NOT NULL REFERENCES feeds(id) ON DELETE CASCADE,
+  status_code int,
+  error varchar,
+  error_message text,
+  latency_ms int NOT NULL,
+  byte_count int,
+  created_date timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS feed_fetch_log_feed_id_created_date_idx ON feed_fetch_log (feed_id, created_date DESC);
+
+ALTER TABLE feeds
+ADD COLUMN last_success_at timestamptz,
+ADD COLUMN consecutive_failures int NOT NULL DEFAULT 0,
+ADD COLUMN disabled boolean NOT NULL DEFAULT false;
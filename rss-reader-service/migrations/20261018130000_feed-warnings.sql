@@ -0,0 +1,6 @@
+-- Keep the entries each fetch had to skip or repair
+ALTER TABLE feed_fetch_log
+ADD COLUMN warnings text[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE feeds
+ADD COLUMN warning_count int NOT NULL DEFAULT 0;
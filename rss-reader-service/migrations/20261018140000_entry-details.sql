@@ -0,0 +1,8 @@
+-- Keep what feeds say about each entry beyond its title and link
+ALTER TABLE entries
+ADD COLUMN updated_date timestamptz,
+ADD COLUMN summary text,
+ADD COLUMN content_html text,
+ADD COLUMN authors text[] NOT NULL DEFAULT '{}',
+ADD COLUMN categories text[] NOT NULL DEFAULT '{}',
+ADD COLUMN comments_url varchar;
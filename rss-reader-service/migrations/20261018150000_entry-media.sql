@@ -0,0 +1,8 @@
+-- Podcast enclosures, iTunes episode details and Media RSS thumbnails
+ALTER TABLE entries
+ADD COLUMN enclosures jsonb NOT NULL DEFAULT '[]',
+ADD COLUMN duration int,
+ADD COLUMN episode int,
+ADD COLUMN season int,
+ADD COLUMN image_url varchar,
+ADD COLUMN thumbnails text[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Plain text opening of each entry, cut from its sanitized summary or content
+ALTER TABLE entries
+ADD COLUMN preview text;
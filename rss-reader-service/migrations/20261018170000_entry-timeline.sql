@@ -0,0 +1,2 @@
+-- Entries newest first across all feeds, for paging through the timeline
+CREATE INDEX IF NOT EXISTS entries_created_date_id_idx ON entries (created_date DESC, id DESC);
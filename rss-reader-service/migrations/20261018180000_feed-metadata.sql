@@ -0,0 +1,6 @@
+-- What each feed's channel says about itself, refreshed on every successful fetch
+ALTER TABLE feeds
+ADD COLUMN title varchar,
+ADD COLUMN site_url varchar,
+ADD COLUMN description text,
+ADD COLUMN icon_url varchar;
@@ -2,7 +2,7 @@ use std::fmt;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, FromRow};
+use sqlx::{PgConnection, PgExecutor, PgPool, FromRow};
 
 #[derive(Debug)]
 pub enum CacheError {
@@ -29,12 +29,16 @@ impl From<sqlx::Error> for CacheError {
 pub struct CacheInput {
     pub name: String,
     pub xml_string: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
 pub struct CacheValue {
   pub name: String,
   pub xml_string: String,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
   pub created_date: DateTime<Utc>,
 }
 
@@ -70,10 +74,17 @@ impl CacheDataSource {
     println!("Caching feed: {}", cache_value.name);
 
     if let Err(e) = sqlx::query(
-        "INSERT INTO cache (name, xml_string) VALUES ($1, $2);"
+        "INSERT INTO cache (name, xml_string, etag, last_modified) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name) DO UPDATE SET
+          xml_string = EXCLUDED.xml_string,
+          etag = EXCLUDED.etag,
+          last_modified = EXCLUDED.last_modified,
+          created_date = CURRENT_TIMESTAMP;"
     )
     .bind(&cache_value.name)
     .bind(&cache_value.xml_string)
+    .bind(&cache_value.etag)
+    .bind(&cache_value.last_modified)
     .execute(&self.db)
     .await
     {
@@ -83,10 +94,29 @@ impl CacheDataSource {
     Ok(())
   }
 
+  pub async fn refresh_cached_value(self, name: String) -> Result<(), CacheError> {
+    println!("Refreshing unmodified cached feed: {}", name);
+
+    if let Err(e) = sqlx::query(
+        "UPDATE cache SET created_date = CURRENT_TIMESTAMP WHERE name = $1;"
+    )
+    .bind(&name)
+    .execute(&self.db)
+    .await
+    {
+        return Err(CacheError::Database(e));
+    }
+
+    Ok(())
+  }
+
+
+  // Rows are kept for as long as their feed exists so the validators can be reused by a
+  // conditional GET, however long that feed's polling interval has grown
   pub async fn clear_cache(self) -> Result<(), CacheError> {
     let stale_cache = match sqlx::query_as::<_, CacheValue>(
       "SELECT * FROM cache 
-        WHERE created_date < NOW() - INTERVAL '10 minutes';")
+        WHERE name NOT IN (SELECT name FROM feeds);")
       .fetch_all(&self.db)
       .await {
         Ok(res) => res,
@@ -101,7 +131,7 @@ impl CacheDataSource {
 
       if let Err(e) = sqlx::query_as::<_, CacheValue>(
         "DELETE FROM cache
-          WHERE created_date < NOW() - INTERVAL '10 minutes';"
+          WHERE name NOT IN (SELECT name FROM feeds);"
       )
         .fetch_all(&self.db)
         .await {
@@ -111,4 +141,32 @@ impl CacheDataSource {
 
     Ok(())
   }
-}
\ No newline at end of file
+}
+
+// Forgets the cached body and validators, so the next fetch starts from scratch. Takes any
+// executor, so a feed's URL change and the cleanup commit together.
+pub async fn delete_cached_value(db: impl PgExecutor<'_>, name: &str) -> Result<(), CacheError> {
+  println!("Deleting cached feed: {}", name);
+
+  sqlx::query("DELETE FROM cache WHERE name = $1;")
+    .bind(name)
+    .execute(db)
+    .await?;
+
+  Ok(())
+}
+
+// Keeps a renamed feed's validators, inside the rename's transaction. Anything left under the new
+// name belonged to a feed that no longer exists.
+pub async fn rename_cached_value(conn: &mut PgConnection, name: &str, new_name: &str) -> Result<(), CacheError> {
+  println!("Renaming cached feed: {} -> {}", name, new_name);
+
+  delete_cached_value(&mut *conn, new_name).await?;
+  sqlx::query("UPDATE cache SET name = $2 WHERE name = $1;")
+    .bind(name)
+    .bind(new_name)
+    .execute(&mut *conn)
+    .await?;
+
+  Ok(())
+}
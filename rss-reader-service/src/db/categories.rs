@@ -0,0 +1,181 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, FromRow};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoryInput {
+  pub name: String,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Category {
+  pub id: i32,
+  pub name: String,
+  pub feed_count: i64,
+}
+
+const CATEGORY_SELECT: &str = "SELECT categories.id, categories.name, COUNT(feeds.id) AS feed_count
+      FROM categories
+      LEFT JOIN feeds
+      ON
+      feeds.category_id = categories.id";
+
+fn category_write_error(e: sqlx::Error, action: &str) -> (StatusCode, String) {
+  match e.as_database_error() {
+    Some(db_error) if db_error.is_unique_violation() => {
+      (StatusCode::CONFLICT, "A category with this name already exists".to_string())
+    },
+    _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error while {action}: {e}")),
+  }
+}
+
+pub struct CategoryDataSource {
+  db: PgPool
+}
+
+impl CategoryDataSource {
+  pub fn new(db: PgPool) -> Self {
+    Self {
+      db
+    }
+  }
+
+  pub async fn get_categories(&self) -> Result<Vec<Category>, (StatusCode, String)> {
+    sqlx::query_as::<_, Category>(
+      &format!("{CATEGORY_SELECT}
+      GROUP BY categories.id
+      ORDER BY categories.name;"))
+      .fetch_all(&self.db)
+      .await
+      .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+  }
+
+  pub async fn get_category(&self, id: i32) -> Result<Category, (StatusCode, String)> {
+    let res = sqlx::query_as::<_, Category>(
+      &format!("{CATEGORY_SELECT}
+      WHERE categories.id = $1
+      GROUP BY categories.id;"))
+      .bind(id)
+      .fetch_optional(&self.db)
+      .await
+      .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    res.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Category not found: {id}")))
+  }
+
+  pub async fn get_category_by_name(&self, name: &str) -> Result<Category, (StatusCode, String)> {
+    let res = sqlx::query_as::<_, Category>(
+      &format!("{CATEGORY_SELECT}
+      WHERE categories.name = $1
+      GROUP BY categories.id;"))
+      .bind(name)
+      .fetch_optional(&self.db)
+      .await
+      .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    res.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Category not found: {name}")))
+  }
+
+  pub async fn create_category(&self, category: CategoryInput) -> Result<Category, (StatusCode, String)> {
+    println!("Creating new category: {}", category.name);
+
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO categories (name)
+        VALUES ($1)
+        RETURNING id"
+    )
+    .bind(&category.name)
+    .fetch_one(&self.db)
+    .await
+    .map_err(|e| category_write_error(e, "inserting a category"))?;
+
+    self.get_category(id).await
+  }
+
+  pub async fn rename_category(&self, id: i32, category: CategoryInput) -> Result<Category, (StatusCode, String)> {
+    println!("Renaming category {}: {}", id, category.name);
+
+    let res = sqlx::query("UPDATE categories SET name = $2 WHERE id = $1")
+      .bind(id)
+      .bind(&category.name)
+      .execute(&self.db)
+      .await
+      .map_err(|e| category_write_error(e, "renaming a category"))?;
+
+    if res.rows_affected() == 0 {
+      return Err((StatusCode::NOT_FOUND, format!("Category not found: {id}")));
+    }
+
+    self.get_category(id).await
+  }
+
+  // Feeds cascade with their category, so one that still has feeds has to be merged away instead
+  pub async fn delete_category(&self, id: i32) -> Result<(), (StatusCode, String)> {
+    println!("Deleting category: {}", id);
+
+    let category = self.get_category(id).await?;
+    if category.feed_count > 0 {
+      return Err((
+        StatusCode::CONFLICT,
+        format!("Category {} still has {} feeds, merge it into another category first", category.name, category.feed_count)
+      ));
+    }
+
+    self.delete_if_empty(id).await
+  }
+
+  // Moves every feed from one category to another and deletes the emptied one
+  pub async fn merge_categories(&self, id: i32, into: i32) -> Result<Category, (StatusCode, String)> {
+    println!("Merging category {} into {}", id, into);
+
+    if id == into {
+      return Err((StatusCode::BAD_REQUEST, "Can't merge a category into itself".to_string()));
+    }
+    self.get_category(id).await?;
+    self.get_category(into).await?;
+
+    if let Err(e) = sqlx::query("UPDATE feeds SET category_id = $2 WHERE category_id = $1")
+      .bind(id)
+      .bind(into)
+      .execute(&self.db)
+      .await
+    {
+      return Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error while moving feeds between categories: {e}"),
+      ));
+    }
+
+    self.delete_if_empty(id).await?;
+    self.get_category(into).await
+  }
+
+  // Drops a category once no feed uses it, called whenever a feed leaves one
+  pub async fn delete_if_empty(&self, id: i32) -> Result<(), (StatusCode, String)> {
+    delete_empty_category(&self.db, id).await
+  }
+}
+
+// Takes any executor, so a feed's move out of a category and the cleanup commit together
+pub async fn delete_empty_category(db: impl PgExecutor<'_>, id: i32) -> Result<(), (StatusCode, String)> {
+  let res = sqlx::query(
+      "DELETE FROM categories
+      WHERE id = $1
+      AND NOT EXISTS (SELECT 1 FROM feeds WHERE category_id = $1)"
+  )
+  .bind(id)
+  .execute(db)
+  .await
+  .map_err(|e| {
+    (
+      StatusCode::INTERNAL_SERVER_ERROR,
+      format!("Error while deleting category: {e}"),
+    )
+  })?;
+
+  if res.rows_affected() > 0 {
+    println!("Deleted empty category: {}", id);
+  }
+
+  Ok(())
+}
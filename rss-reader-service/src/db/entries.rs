@@ -0,0 +1,254 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgPool, FromRow};
+
+use super::FeedFilter;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Enclosure {
+  pub url: String,
+  pub mime_type: Option<String>,
+  // Size in bytes
+  pub length: Option<i64>,
+  // Seconds, when the publisher gave this attachment a length. Entries stored before it was
+  // tracked per attachment only have their entry's duration.
+  #[serde(default)]
+  pub duration: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntryInput {
+  // Stable identity of the entry within its feed: guid, atom id, or link as a fallback
+  pub identity: String,
+  pub title: String,
+  pub url: String,
+  // Entries without a usable date are dated when first seen
+  pub created_date: Option<DateTime<Utc>>,
+  pub updated_date: Option<DateTime<Utc>>,
+  // Summary and full content as HTML, as the feed provided them
+  pub summary: Option<String>,
+  pub content_html: Option<String>,
+  // Plain text opening of the summary or content
+  pub preview: Option<String>,
+  pub authors: Vec<String>,
+  pub categories: Vec<String>,
+  pub comments_url: Option<String>,
+  // Podcast and media attachments
+  pub enclosures: Vec<Enclosure>,
+  // Episode length in seconds
+  pub duration: Option<i32>,
+  pub episode: Option<i32>,
+  pub season: Option<i32>,
+  pub image_url: Option<String>,
+  pub thumbnails: Vec<String>,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Entry {
+  pub id: i32,
+  pub feed_id: i32,
+  pub identity: String,
+  pub title: String,
+  pub url: String,
+  pub created_date: DateTime<Utc>,
+  pub updated_date: Option<DateTime<Utc>>,
+  pub summary: Option<String>,
+  pub content_html: Option<String>,
+  pub preview: Option<String>,
+  pub authors: Vec<String>,
+  pub categories: Vec<String>,
+  pub comments_url: Option<String>,
+  pub enclosures: Json<Vec<Enclosure>>,
+  pub duration: Option<i32>,
+  pub episode: Option<i32>,
+  pub season: Option<i32>,
+  pub image_url: Option<String>,
+  pub thumbnails: Vec<String>,
+}
+
+// Narrows the entries read back for a feed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EntryFilter {
+  pub since: Option<DateTime<Utc>>,
+  pub until: Option<DateTime<Utc>>,
+  // Searched for in titles and summaries, ignoring case
+  pub q: Option<String>,
+  pub has_enclosure: bool,
+}
+
+impl EntryFilter {
+  // ILIKE pattern matching the search text literally
+  fn search_pattern(&self) -> Option<String> {
+    self.q.as_deref()
+      .map(str::trim)
+      .filter(|q| !q.is_empty())
+      .map(|q| format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")))
+  }
+}
+
+// An entry alongside the feed it came from, for views that mix feeds
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct TimelineEntry {
+  #[sqlx(flatten)]
+  #[serde(flatten)]
+  pub entry: Entry,
+  pub feed_name: String,
+  pub feed_url: String,
+  pub category: String,
+}
+
+pub struct EntryDataSource {
+  db: PgPool
+}
+
+impl EntryDataSource {
+  pub fn new(db: PgPool) -> Self {
+    Self {
+      db
+    }
+  }
+
+  pub async fn get_entries(&self, feed_id: i32, filter: &EntryFilter, max_entries: i64) -> Result<Vec<Entry>, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, Entry>(
+      "SELECT * FROM entries
+      WHERE feed_id = $1
+      AND ($2::timestamptz IS NULL OR created_date >= $2)
+      AND ($3::timestamptz IS NULL OR created_date <= $3)
+      AND ($4::text IS NULL OR title ILIKE $4 OR summary ILIKE $4)
+      AND (NOT $5 OR jsonb_array_length(enclosures) > 0)
+      ORDER BY created_date DESC
+      LIMIT $6;")
+      .bind(feed_id)
+      .bind(filter.since)
+      .bind(filter.until)
+      .bind(filter.search_pattern())
+      .bind(filter.has_enclosure)
+      .bind(max_entries)
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  // Newest first across every feed the filters allow. Entries sharing a date are ordered by id so
+  // that paging from (created_date, id) never skips or repeats one.
+  pub async fn get_timeline(
+    &self,
+    feeds: &FeedFilter,
+    filter: &EntryFilter,
+    before: Option<(DateTime<Utc>, i32)>,
+    limit: i64
+  ) -> Result<Vec<TimelineEntry>, (StatusCode, String)> {
+    let (before_date, before_id) = before.unzip();
+
+    let res = match sqlx::query_as::<_, TimelineEntry>(
+      "SELECT entries.*, feeds.name AS feed_name, feeds.url AS feed_url, categories.name AS category
+      FROM entries
+      INNER JOIN feeds ON entries.feed_id = feeds.id
+      INNER JOIN categories ON feeds.category_id = categories.id
+      WHERE ($1::text IS NULL OR categories.name = $1)
+      AND ($2::int IS NULL OR feeds.id = $2)
+      AND ($3::timestamptz IS NULL OR entries.created_date >= $3)
+      AND ($4::timestamptz IS NULL OR entries.created_date <= $4)
+      AND ($5::text IS NULL OR entries.title ILIKE $5 OR entries.summary ILIKE $5)
+      AND (NOT $6 OR jsonb_array_length(entries.enclosures) > 0)
+      AND ($7::timestamptz IS NULL OR (entries.created_date, entries.id) < ($7, $8))
+      ORDER BY entries.created_date DESC, entries.id DESC
+      LIMIT $9;")
+      .bind(&feeds.category)
+      .bind(feeds.feed_id)
+      .bind(filter.since)
+      .bind(filter.until)
+      .bind(filter.search_pattern())
+      .bind(filter.has_enclosure)
+      .bind(before_date)
+      .bind(before_id)
+      .bind(limit)
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  pub async fn upsert_entries(&self, feed_id: i32, entries: Vec<EntryInput>) -> Result<(), (StatusCode, String)> {
+    println!("Upserting {} entries for feed: {}", entries.len(), feed_id);
+
+    let mut tx = self.db.begin().await.map_err(|e| {
+      (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error while starting entries transaction: {e}"),
+      )
+    })?;
+
+    for entry in entries {
+      if let Err(e) = sqlx::query(
+        "INSERT INTO entries (feed_id, identity, title, url, created_date, updated_date, summary, content_html, authors, categories, comments_url,
+          enclosures, duration, episode, season, image_url, thumbnails, preview)
+        VALUES ($1, $2, $3, $4, COALESCE($5, CURRENT_TIMESTAMP), $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+        ON CONFLICT (feed_id, identity) DO UPDATE SET
+          title = EXCLUDED.title,
+          url = EXCLUDED.url,
+          created_date = COALESCE($5, entries.created_date),
+          updated_date = EXCLUDED.updated_date,
+          summary = EXCLUDED.summary,
+          content_html = EXCLUDED.content_html,
+          authors = EXCLUDED.authors,
+          categories = EXCLUDED.categories,
+          comments_url = EXCLUDED.comments_url,
+          enclosures = EXCLUDED.enclosures,
+          duration = EXCLUDED.duration,
+          episode = EXCLUDED.episode,
+          season = EXCLUDED.season,
+          image_url = EXCLUDED.image_url,
+          thumbnails = EXCLUDED.thumbnails,
+          preview = EXCLUDED.preview;"
+      )
+      .bind(feed_id)
+      .bind(&entry.identity)
+      .bind(&entry.title)
+      .bind(&entry.url)
+      .bind(entry.created_date)
+      .bind(entry.updated_date)
+      .bind(&entry.summary)
+      .bind(&entry.content_html)
+      .bind(&entry.authors)
+      .bind(&entry.categories)
+      .bind(&entry.comments_url)
+      .bind(Json(&entry.enclosures))
+      .bind(entry.duration)
+      .bind(entry.episode)
+      .bind(entry.season)
+      .bind(&entry.image_url)
+      .bind(&entry.thumbnails)
+      .bind(&entry.preview)
+      .execute(&mut *tx)
+      .await
+      {
+        return Err((
+          StatusCode::INTERNAL_SERVER_ERROR,
+          format!("Error while upserting entry: {e}"),
+        ));
+      }
+    }
+
+    tx.commit().await.map_err(|e| {
+      (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error while committing entries: {e}"),
+      )
+    })?;
+
+    Ok(())
+  }
+}
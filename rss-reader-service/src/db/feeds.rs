@@ -1,12 +1,46 @@
 use axum::{http::StatusCode, response::IntoResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, FromRow};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction, FromRow};
+
+use super::{delete_cached_value, delete_empty_category, rename_cached_value};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FeedInput {
     pub name: String,
     pub url: String,
     pub category: String,
+    // Minimum seconds between fetches, falls back to the service default when unset
+    #[serde(default)]
+    pub refresh_interval: Option<i32>,
+}
+
+// Fields left out of a PATCH keep their current values
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FeedUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+// Narrows which feeds are read, before any of their entries are
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeedFilter {
+    pub category: Option<String>,
+    pub feed_id: Option<i32>,
+}
+
+// Taken from the channel element on each successful fetch, None until the first one
+#[derive(FromRow, Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct FeedMetadata {
+  pub title: Option<String>,
+  // The site the feed belongs to
+  pub site_url: Option<String>,
+  pub description: Option<String>,
+  pub icon_url: Option<String>,
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
@@ -15,6 +49,41 @@ pub struct Feed {
   pub name: String,
   pub url: String,
   pub category: String,
+  pub refresh_interval: Option<i32>,
+  pub refresh_hint: Option<i32>,
+  pub unchanged_fetches: i32,
+  pub next_fetch_at: DateTime<Utc>,
+  pub last_success_at: Option<DateTime<Utc>>,
+  pub consecutive_failures: i32,
+  pub disabled: bool,
+  // Warnings left by the latest successful fetch
+  pub warning_count: i32,
+  #[sqlx(flatten)]
+  #[serde(flatten)]
+  pub metadata: FeedMetadata,
+}
+
+const FEED_SELECT: &str = "SELECT feeds.id, feeds.name, feeds.url, categories.name AS category,
+      feeds.refresh_interval, feeds.refresh_hint, feeds.unchanged_fetches, feeds.next_fetch_at,
+      feeds.last_success_at, feeds.consecutive_failures, feeds.disabled, feeds.warning_count,
+      feeds.title, feeds.site_url, feeds.description, feeds.icon_url
+      FROM feeds
+      INNER JOIN categories
+      ON
+      feeds.category_id = categories.id";
+
+// Names and URLs are unique, which is worth a 409 rather than a 500
+fn feed_write_error(e: sqlx::Error, action: &str) -> (StatusCode, String) {
+    let constraint = e.as_database_error()
+        .filter(|db_error| db_error.is_unique_violation())
+        .map(|db_error| db_error.constraint().unwrap_or_default().to_string());
+
+    match constraint.as_deref() {
+        Some("feeds_name_key") => (StatusCode::CONFLICT, "A feed with this name already exists".to_string()),
+        Some("feeds_url_key") => (StatusCode::CONFLICT, "A feed with this url already exists".to_string()),
+        Some(_) => (StatusCode::CONFLICT, format!("Error while {action}: {e}")),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error while {action}: {e}")),
+    }
 }
 
 pub struct FeedDataSource {
@@ -28,13 +97,9 @@ impl FeedDataSource {
     }
   }
 
-  pub async fn get_feeds(&self) -> Result<Vec<Feed>, impl IntoResponse> {    
+  pub async fn get_feeds(&self) -> Result<Vec<Feed>, (StatusCode, String)> {    
     let res = match sqlx::query_as::<_, Feed>(
-      "SELECT feeds.id, feeds.name, feeds.url, categories.name AS category
-      FROM feeds
-      INNER JOIN categories
-      ON
-      feeds.category_id = categories.id;")
+      &format!("{FEED_SELECT};"))
       .fetch_all(&self.db)
       .await {
         Ok(res) => res,
@@ -46,6 +111,187 @@ impl FeedDataSource {
     Ok(res)
   }
 
+  pub async fn get_filtered_feeds(&self, filter: &FeedFilter) -> Result<Vec<Feed>, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, Feed>(
+      &format!("{FEED_SELECT}
+      WHERE ($1::text IS NULL OR categories.name = $1)
+      AND ($2::int IS NULL OR feeds.id = $2);"))
+      .bind(&filter.category)
+      .bind(filter.feed_id)
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  pub async fn get_due_feeds(&self) -> Result<Vec<Feed>, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, Feed>(
+      &format!("{FEED_SELECT}
+      WHERE feeds.next_fetch_at <= NOW()
+      AND NOT feeds.disabled;"))
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  pub async fn schedule_next_fetch(
+    &self,
+    id: i32,
+    refresh_hint: Option<i32>,
+    unchanged_fetches: i32,
+    next_fetch_at: DateTime<Utc>
+  ) -> Result<(), (StatusCode, String)> {
+    if let Err(e) = sqlx::query(
+        "UPDATE feeds
+        SET refresh_hint = $2, unchanged_fetches = $3, next_fetch_at = $4
+        WHERE id = $1"
+    )
+    .bind(id)
+    .bind(refresh_hint)
+    .bind(unchanged_fetches)
+    .bind(next_fetch_at)
+    .execute(&self.db)
+    .await
+    {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while scheduling feed fetch: {e}"),
+        ));
+    }
+
+    Ok(())
+  }
+
+  pub async fn get_unhealthy_feeds(&self) -> Result<Vec<Feed>, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, Feed>(
+      &format!("{FEED_SELECT}
+      WHERE feeds.consecutive_failures > 0
+      OR feeds.disabled
+      OR feeds.warning_count > 0
+      ORDER BY feeds.consecutive_failures DESC, feeds.warning_count DESC;"))
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  pub async fn record_fetch_success(&self, id: i32, warning_count: i32) -> Result<(), (StatusCode, String)> {
+    if let Err(e) = sqlx::query(
+        "UPDATE feeds
+        SET last_success_at = CURRENT_TIMESTAMP, consecutive_failures = 0, warning_count = $2
+        WHERE id = $1"
+    )
+    .bind(id)
+    .bind(warning_count)
+    .execute(&self.db)
+    .await
+    {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while recording fetch success: {e}"),
+        ));
+    }
+
+    Ok(())
+  }
+
+  pub async fn update_metadata(&self, id: i32, metadata: &FeedMetadata) -> Result<(), (StatusCode, String)> {
+    if let Err(e) = sqlx::query(
+        "UPDATE feeds
+        SET title = $2, site_url = $3, description = $4, icon_url = $5
+        WHERE id = $1"
+    )
+    .bind(id)
+    .bind(&metadata.title)
+    .bind(&metadata.site_url)
+    .bind(&metadata.description)
+    .bind(&metadata.icon_url)
+    .execute(&self.db)
+    .await
+    {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while updating feed metadata: {e}"),
+        ));
+    }
+
+    Ok(())
+  }
+
+  // Returns whether the feed is now disabled, a disable_after of 0 never disables
+  pub async fn record_fetch_failure(&self, id: i32, disable_after: i32) -> Result<bool, (StatusCode, String)> {
+    sqlx::query_scalar(
+        "UPDATE feeds
+        SET consecutive_failures = consecutive_failures + 1,
+          disabled = disabled OR ($2 > 0 AND consecutive_failures + 1 >= $2)
+        WHERE id = $1
+        RETURNING disabled"
+    )
+    .bind(id)
+    .bind(disable_after)
+    .fetch_one(&self.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while recording fetch failure: {e}"),
+        )
+    })
+  }
+
+  pub async fn enable_feed(&self, id: i32) -> Result<Feed, (StatusCode, String)> {
+    println!("Enabling feed: {}", id);
+
+    let res = sqlx::query(
+        "UPDATE feeds
+        SET disabled = false, consecutive_failures = 0, next_fetch_at = CURRENT_TIMESTAMP
+        WHERE id = $1"
+    )
+    .bind(id)
+    .execute(&self.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while enabling feed: {e}"),
+        )
+    })?;
+
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, format!("Feed not found: {id}")));
+    }
+
+    let res = match sqlx::query_as::<_, Feed>(
+      &format!("{FEED_SELECT}
+      WHERE feeds.id = $1;"))
+      .bind(id)
+      .fetch_one(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
   pub async fn batch_create_feeds(&self, feeds: Vec<FeedInput>) -> Result<Vec<Feed>, (StatusCode, String)> {
     println!("Batch creating feeds: {:?}", feeds);
 
@@ -63,64 +309,157 @@ impl FeedDataSource {
     Ok(created_feeds)
 }
 
-  pub async fn create_feed(&self, feed: FeedInput) -> Result<Feed, (StatusCode, String)> {
-    println!("Creating new feed: {:?}", feed);
-
+  // Id of the named category, creating it when it's new. Runs inside the caller's transaction so a
+  // feed write that fails doesn't leave the new category behind empty.
+  async fn upsert_category(conn: &mut PgConnection, category: &str) -> Result<i32, (StatusCode, String)> {
     let new_category_id = sqlx::query_scalar(
         "INSERT INTO categories (name)
         VALUES ($1)
         ON CONFLICT (name) DO NOTHING
         RETURNING id"
     )
-    .bind(&feed.category)
-    .fetch_optional(&self.db)
+    .bind(category)
+    .fetch_optional(&mut *conn)
     .await;
 
-    let category_id: i32 = match new_category_id {
-        Ok(Some(id)) => id,
+    match new_category_id {
+        Ok(Some(id)) => Ok(id),
         Ok(None) => {
             sqlx::query_scalar("SELECT id FROM categories WHERE name = $1")
-                .bind(&feed.category)
-                .fetch_one(&self.db)
+                .bind(category)
+                .fetch_one(&mut *conn)
                 .await
                 .map_err(|e| {
                     (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         format!("Error fetching existing category ID: {e}"),
                     )
-                })?
+                })
         },
         Err(e) => {
-            return Err((
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Error while inserting category: {e}"),
-            ));
+            ))
         }
+    }
+  }
+
+  async fn begin(&self) -> Result<Transaction<'static, Postgres>, (StatusCode, String)> {
+    self.db.begin().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while starting feed transaction: {e}"),
+        )
+    })
+  }
+
+  async fn commit(tx: Transaction<'static, Postgres>) -> Result<(), (StatusCode, String)> {
+    tx.commit().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while committing feed transaction: {e}"),
+        )
+    })
+  }
+
+  pub async fn get_feed(&self, id: i32) -> Result<Feed, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, Feed>(
+      &format!("{FEED_SELECT}
+      WHERE feeds.id = $1;"))
+      .bind(id)
+      .fetch_optional(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    res.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Feed not found: {id}")))
+  }
+
+  pub async fn update_feed(&self, id: i32, feed: FeedUpdate) -> Result<Feed, (StatusCode, String)> {
+    println!("Updating feed {}: {:?}", id, feed);
+
+    let mut tx = self.begin().await?;
+    let category_id = match &feed.category {
+        Some(category) => Some(Self::upsert_category(&mut tx, category).await?),
+        None => None,
     };
 
+    // A new URL is a different document, so it's fetched right away rather than on the old schedule
+    let previous: Option<(i32, String, String)> = sqlx::query_as(
+        "UPDATE feeds
+        SET name = COALESCE($2, feeds.name),
+          url = COALESCE($3, feeds.url),
+          category_id = COALESCE($4, feeds.category_id),
+          next_fetch_at = CASE WHEN $3 <> feeds.url THEN CURRENT_TIMESTAMP ELSE feeds.next_fetch_at END,
+          unchanged_fetches = CASE WHEN $3 <> feeds.url THEN 0 ELSE feeds.unchanged_fetches END
+        FROM feeds AS previous
+        WHERE feeds.id = $1 AND previous.id = $1
+        RETURNING previous.category_id, previous.name, previous.url"
+    )
+    .bind(id)
+    .bind(&feed.name)
+    .bind(&feed.url)
+    .bind(category_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| feed_write_error(e, "updating a feed"))?;
+
+    // Returning early drops the transaction, which rolls back any category it created
+    let Some((previous_category_id, previous_name, previous_url)) = previous else {
+        return Err((StatusCode::NOT_FOUND, format!("Feed not found: {id}")));
+    };
+    if category_id.is_some_and(|category_id| category_id != previous_category_id) {
+        delete_empty_category(&mut *tx, previous_category_id).await?;
+    }
+
+    // The cache is keyed by name and holds the old URL's body and validators
+    let name = feed.name.as_deref().unwrap_or(&previous_name);
+    let cache_result = if feed.url.as_ref().is_some_and(|url| *url != previous_url) {
+        delete_cached_value(&mut *tx, &previous_name).await
+    } else if name != previous_name {
+        rename_cached_value(&mut tx, &previous_name, name).await
+    } else {
+        Ok(())
+    };
+    cache_result.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while updating the feed's cache: {e}"),
+        )
+    })?;
+    Self::commit(tx).await?;
+
+    self.get_feed(id).await
+  }
+
+  pub async fn create_feed(&self, feed: FeedInput) -> Result<Feed, (StatusCode, String)> {
+    println!("Creating new feed: {:?}", feed);
+
+    let mut tx = self.begin().await?;
+    let category_id = Self::upsert_category(&mut tx, &feed.category).await?;
+
     if let Err(e) = sqlx::query(
-        "INSERT INTO feeds (name, url, category_id)
-        VALUES ($1, $2, $3)"
+        "INSERT INTO feeds (name, url, category_id, refresh_interval)
+        VALUES ($1, $2, $3, $4)"
     )
     .bind(&feed.name)
     .bind(&feed.url)
     .bind(category_id)
-    .execute(&self.db)
+    .bind(feed.refresh_interval)
+    .execute(&mut *tx)
     .await
     {
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error while inserting a feed: {e}"),
-        ));
+        return Err(feed_write_error(e, "inserting a feed"));
     }
+    Self::commit(tx).await?;
 
     let res = match sqlx::query_as::<_, Feed>(
-      "SELECT feeds.id, feeds.name, feeds.url, categories.name AS category
-      FROM feeds
-      INNER JOIN categories
-      ON
-      feeds.category_id = categories.id
-      WHERE feeds.name = $1;")
+      &format!("{FEED_SELECT}
+      WHERE feeds.name = $1;"))
       .bind(feed.name)
       .fetch_one(&self.db)
       .await {
@@ -136,15 +475,132 @@ impl FeedDataSource {
   pub async fn delete_feed(&self, id: i32) -> Result<impl IntoResponse, impl IntoResponse> {
     println!("Deleting feed: {}", id);
 
-    if let Err(e) = sqlx::query_as::<_,Feed>("DELETE FROM feeds WHERE id = $1")
+    // The cleanup shares the delete's transaction, so a feed moving into the category meanwhile keeps it
+    let mut tx = self.begin().await?;
+    let category_id: Option<i32> = match sqlx::query_scalar("DELETE FROM feeds WHERE id = $1 RETURNING category_id")
       .bind(id)
-      .fetch_all(&self.db)
+      .fetch_optional(&mut *tx)
       .await {
-        return Err((
-          StatusCode::INTERNAL_SERVER_ERROR,
-          format!("Error while deleting feed: {e}"))
-        );
-      }
+        Ok(category_id) => category_id,
+        Err(e) => {
+          return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error while deleting feed: {e}"))
+          );
+        }
+      };
+
+    if let Some(category_id) = category_id {
+      delete_empty_category(&mut *tx, category_id).await?;
+    }
+    Self::commit(tx).await?;
     Ok(StatusCode::OK)
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CacheDataSource, CacheInput, CategoryDataSource};
+
+    fn feed(name: &str, category: &str) -> FeedInput {
+        FeedInput {
+            name: name.to_string(),
+            url: format!("https://example.org/{name}.xml"),
+            category: category.to_string(),
+            refresh_interval: None,
+        }
+    }
+
+    async fn category_names(db: PgPool) -> Vec<String> {
+        CategoryDataSource::new(db).get_categories().await.unwrap()
+            .into_iter()
+            .map(|category| category.name)
+            .collect()
+    }
+
+    // Run against a scratch database with DATABASE_URL=postgres://... cargo test -- --ignored
+    #[sqlx::test]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn test_update_missing_feed_keeps_no_new_category(db: PgPool) {
+        let feed_db = FeedDataSource::new(db.clone());
+        let update = FeedUpdate { category: Some("Brand New".to_string()), ..FeedUpdate::default() };
+
+        let (status, _) = feed_db.update_feed(404, update).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(category_names(db).await.is_empty());
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn test_conflicting_feed_writes_keep_no_new_category(db: PgPool) {
+        let feed_db = FeedDataSource::new(db.clone());
+        feed_db.create_feed(feed("rust", "Tech")).await.unwrap();
+        let go = feed_db.create_feed(feed("go", "Tech")).await.unwrap();
+
+        let update = FeedUpdate { name: Some("rust".to_string()), category: Some("Brand New".to_string()), ..FeedUpdate::default() };
+        let (status, _) = feed_db.update_feed(go.id, update).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = feed_db.create_feed(feed("rust", "Also New")).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(category_names(db).await, vec!["Tech"]);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn test_update_feed_moves_category(db: PgPool) {
+        let feed_db = FeedDataSource::new(db.clone());
+        let rust = feed_db.create_feed(feed("rust", "Tech")).await.unwrap();
+
+        let update = FeedUpdate { category: Some("Languages".to_string()), ..FeedUpdate::default() };
+        let updated = feed_db.update_feed(rust.id, update).await.unwrap();
+        assert_eq!(updated.category, "Languages");
+        assert_eq!(category_names(db).await, vec!["Languages"]);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn test_delete_feed_drops_only_empty_category(db: PgPool) {
+        let feed_db = FeedDataSource::new(db.clone());
+        let rust = feed_db.create_feed(feed("rust", "Tech")).await.unwrap();
+        let go = feed_db.create_feed(feed("go", "Tech")).await.unwrap();
+
+        assert!(feed_db.delete_feed(rust.id).await.is_ok());
+        assert_eq!(category_names(db.clone()).await, vec!["Tech"]);
+
+        assert!(feed_db.delete_feed(go.id).await.is_ok());
+        assert!(category_names(db).await.is_empty());
+    }
+
+    async fn cache(db: &PgPool, name: &str) {
+        let value = CacheInput { name: name.to_string(), xml_string: "<rss />".to_string(), etag: Some("\"v1\"".to_string()), last_modified: None };
+        CacheDataSource::new(db).cache_value(value).await.unwrap();
+    }
+
+    async fn cached_etag(db: &PgPool, name: &str) -> Option<String> {
+        CacheDataSource::new(db).get_cached_value(name.to_string()).await.unwrap()
+            .and_then(|value| value.etag)
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn test_update_feed_moves_cache(db: PgPool) {
+        let feed_db = FeedDataSource::new(db.clone());
+        let rust = feed_db.create_feed(feed("rust", "Tech")).await.unwrap();
+        let go = feed_db.create_feed(feed("go", "Tech")).await.unwrap();
+        cache(&db, "rust").await;
+        cache(&db, "go").await;
+
+        // A rename keeps the validators under the new name
+        let update = FeedUpdate { name: Some("rustlang".to_string()), ..FeedUpdate::default() };
+        feed_db.update_feed(rust.id, update).await.unwrap();
+        assert_eq!(cached_etag(&db, "rust").await, None);
+        assert_eq!(cached_etag(&db, "rustlang").await.as_deref(), Some("\"v1\""));
+
+        // A new URL is a different document, so its old body is forgotten
+        let update = FeedUpdate { url: Some("https://example.org/golang.xml".to_string()), ..FeedUpdate::default() };
+        feed_db.update_feed(go.id, update).await.unwrap();
+        assert_eq!(cached_etag(&db, "go").await, None);
+    }
+}
@@ -0,0 +1,110 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, FromRow};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FetchLogInput {
+  pub feed_id: i32,
+  pub status_code: Option<i32>,
+  // FetchXmlError variant of a failed attempt
+  pub error: Option<String>,
+  pub error_message: Option<String>,
+  pub latency_ms: i32,
+  pub byte_count: Option<i32>,
+  // Entries that were skipped or repaired while parsing
+  pub warnings: Vec<String>,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct FetchLog {
+  pub id: i32,
+  pub feed_id: i32,
+  pub status_code: Option<i32>,
+  pub error: Option<String>,
+  pub error_message: Option<String>,
+  pub latency_ms: i32,
+  pub byte_count: Option<i32>,
+  pub warnings: Vec<String>,
+  pub created_date: DateTime<Utc>,
+}
+
+pub struct FetchLogDataSource {
+  db: PgPool
+}
+
+impl FetchLogDataSource {
+  pub fn new(db: PgPool) -> Self {
+    Self {
+      db
+    }
+  }
+
+  pub async fn get_fetch_logs(&self, feed_id: i32, limit: i64) -> Result<Vec<FetchLog>, (StatusCode, String)> {
+    let res = match sqlx::query_as::<_, FetchLog>(
+      "SELECT * FROM feed_fetch_log
+      WHERE feed_id = $1
+      ORDER BY created_date DESC
+      LIMIT $2;")
+      .bind(feed_id)
+      .bind(limit)
+      .fetch_all(&self.db)
+      .await {
+        Ok(res) => res,
+        Err(e) => {
+          return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+      };
+
+    Ok(res)
+  }
+
+  pub async fn create_fetch_log(&self, log: FetchLogInput) -> Result<(), (StatusCode, String)> {
+    if let Err(e) = sqlx::query(
+      "INSERT INTO feed_fetch_log (feed_id, status_code, error, error_message, latency_ms, byte_count, warnings)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+    .bind(log.feed_id)
+    .bind(log.status_code)
+    .bind(&log.error)
+    .bind(&log.error_message)
+    .bind(log.latency_ms)
+    .bind(log.byte_count)
+    .bind(&log.warnings)
+    .execute(&self.db)
+    .await
+    {
+      return Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error while inserting fetch log: {e}"),
+      ));
+    }
+
+    Ok(())
+  }
+
+  // Deletes all but the feed's newest rows, returning how many went
+  pub async fn prune_fetch_logs(&self, feed_id: i32, keep: i64) -> Result<u64, (StatusCode, String)> {
+    sqlx::query(
+      "DELETE FROM feed_fetch_log
+      WHERE feed_id = $1
+      AND id NOT IN (
+        SELECT id FROM feed_fetch_log
+        WHERE feed_id = $1
+        ORDER BY created_date DESC
+        LIMIT $2
+      )"
+    )
+    .bind(feed_id)
+    .bind(keep)
+    .execute(&self.db)
+    .await
+    .map(|res| res.rows_affected())
+    .map_err(|e| {
+      (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Error while pruning fetch logs: {e}"),
+      )
+    })
+  }
+}
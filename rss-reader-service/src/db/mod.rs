@@ -1,5 +1,11 @@
 mod feeds;
+mod entries;
+mod fetch_log;
 mod cache;
+mod categories;
 
 pub use feeds::*;
-pub use cache::*;
\ No newline at end of file
+pub use entries::*;
+pub use fetch_log::*;
+pub use cache::*;
+pub use categories::*;
\ No newline at end of file
@@ -0,0 +1,15 @@
+use reqwest::Client;
+use shuttle_runtime::SecretStore;
+use sqlx::PgPool;
+
+pub mod auth;
+pub mod db;
+pub mod service;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: PgPool,
+    pub secrets: SecretStore,
+    // Shared by every request to publishers, with the refresh job's timeouts
+    pub client: Client
+}
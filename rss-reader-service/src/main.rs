@@ -1,21 +1,9 @@
-use auth::auth_middleware;
-use axum::{middleware, routing::{delete, get, post}, Router};
-use service::{batch_create_feeds, delete_feed, get_raw_feeds, get_rss_feeds, schedule_cache_clear};
+use axum::{middleware, routing::{delete, get, patch, post}, Router};
+use rss_reader_service::{auth::auth_middleware, AppState};
+use rss_reader_service::service::{batch_create_feeds, create_category, create_feed, delete_category, delete_feed, discover_feed, enable_feed, export_opml, get_categories, get_feed, get_planet_atom, get_planet_json, get_planet_rss, get_raw_feeds, get_rss_feeds, get_timeline, get_unhealthy_feeds, import_opml, merge_categories, preview_feed, rename_category, schedule_cache_clear, schedule_feed_refresh, update_feed, RefreshConfig};
 use shuttle_runtime::SecretStore;
 use sqlx::PgPool;
 
-mod auth;
-mod db;
-mod service;
-
-use crate::service::create_feed;
-
-#[derive(Clone)]
-pub struct AppState {
-    db: PgPool,
-    secrets: SecretStore
-}
-
 #[shuttle_runtime::main]
 async fn main(
     #[shuttle_shared_db::Postgres] db: PgPool,
@@ -27,14 +15,45 @@ async fn main(
     let _ = SecretStore::get(&secrets, "GITHUB_USER_ID")
         .ok_or_else(|| panic!("Missing expected ENV_VAR: GITHUB_USER_ID"));
 
-    let state = AppState { db, secrets };
+    let refresh_config = RefreshConfig::from_secrets(&secrets);
+    let state = AppState { db, secrets, client: refresh_config.http_client() };
 
     schedule_cache_clear(&state.db).await
         .unwrap_or_else(|e| panic!("Failed to start cache clear job: {}", e));
 
+    schedule_feed_refresh(&state.db, state.client.clone(), refresh_config).await
+        .unwrap_or_else(|e| panic!("Failed to start feed refresh job: {}", e));
+
     let unprotected_routes = Router::new()
         .route("/feeds", 
             get(get_rss_feeds)
+        )
+        .route("/feeds.rss",
+            get(get_planet_rss)
+        )
+        .route("/feeds.atom",
+            get(get_planet_atom)
+        )
+        .route("/feeds.json",
+            get(get_planet_json)
+        )
+        .route("/feeds/:id",
+            get(get_feed)
+        )
+        .route("/categories/:name/feed.rss",
+            get(get_planet_rss)
+        )
+        .route("/categories/:name/feed.atom",
+            get(get_planet_atom)
+        )
+        .route("/categories/:name/feed.json",
+            get(get_planet_json)
+        )
+        .route("/timeline",
+            get(get_timeline)
+        )
+        .route("/categories",
+            get(get_categories)
         );
     
     let protected_routes = Router::new()
@@ -45,8 +64,36 @@ async fn main(
         .route("/admin/batch",
             post(batch_create_feeds)
         )
+        .route("/admin/categories",
+            get(get_categories)
+            .post(create_category)
+        )
+        .route("/admin/categories/:id",
+            patch(rename_category)
+            .delete(delete_category)
+        )
+        .route("/admin/categories/:id/merge",
+            post(merge_categories)
+        )
+        .route("/admin/discover",
+            post(discover_feed)
+        )
+        .route("/admin/opml",
+            get(export_opml)
+            .post(import_opml)
+        )
+        .route("/admin/preview",
+            post(preview_feed)
+        )
+        .route("/admin/health",
+            get(get_unhealthy_feeds)
+        )
         .route("/admin/:id",
             delete(delete_feed)
+            .patch(update_feed)
+        )
+        .route("/admin/:id/enable",
+            post(enable_feed)
         )
         .layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
 
@@ -1,10 +1,11 @@
 use std::fmt::Display;
 
-use chrono::{DateTime, Utc};
-use serde::{de, Deserialize, Deserializer, Serialize};
-use serde_json::{from_value, Value};
+use quick_xml::escape::escape;
+use serde::Serialize;
 
-#[derive(Deserialize, Serialize, Debug)]
+use super::{links::resolve_xml_base, media::{enclosure_from, Media}, parser::{read_document, Element, XmlError}, rss::distinct, FeedDate};
+
+#[derive(Serialize, Debug)]
 pub enum AtomError {
     Message(String),
 }
@@ -17,242 +18,360 @@ impl Display for AtomError {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+impl From<XmlError> for AtomError {
+    fn from(e: XmlError) -> Self {
+        AtomError::Message(e.to_string())
+    }
+}
+
+#[derive(Serialize, Debug)]
 pub struct AtomLink {
-    #[serde(rename = "@href")]
     pub href: String,
-    #[serde(rename = "@type")]
-    pub link_type: String,
+    pub rel: Option<String>,
+    pub link_type: Option<String>,
+    // xml:base in effect for the link, which can be its own rather than the entry's
+    pub base: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct AtomTitle {
-  #[serde(rename = "#text")]
-  pub title: String
+impl AtomLink {
+    fn from_element(link: &Element) -> Option<Self> {
+        Some(Self {
+            href: link.attribute("href")?.trim().to_string(),
+            rel: link.attribute("rel").map(|rel| rel.to_string()),
+            link_type: link.attribute("type").map(|link_type| link_type.to_string()),
+            base: link.base.clone(),
+        })
+    }
+
+    // href resolved against the link's xml:base
+    fn url(&self) -> String {
+        resolve_xml_base(self.base.as_deref(), &self.href)
+    }
+
+    // A link without a rel is an alternate link
+    fn is_alternate(&self) -> bool {
+        self.rel.as_deref().is_none_or(|rel| rel == "alternate")
+    }
+
+    fn is_html(&self) -> bool {
+        self.link_type.as_deref().is_none_or(|link_type| link_type == "text/html")
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Serialize, Debug)]
 pub struct AtomEntry {
-    #[serde(deserialize_with = "link")]
-    pub link: String,
-    #[serde(deserialize_with = "updated_date_time")]
-    pub updated: Option<DateTime<Utc>>,
-    #[serde(deserialize_with = "updated_date_time", default)]
-    pub published: Option<DateTime<Utc>>,
-    #[serde(deserialize_with = "title")]
-    pub title: String,
+    // xml:base in effect for the entry
+    pub base: Option<String>,
+    pub id: Option<String>,
+    pub link: Option<String>,
+    pub updated: Option<FeedDate>,
+    pub published: Option<FeedDate>,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub content: Option<String>,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub replies: Option<String>,
+    pub media: Media,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct AtomRoot {
-    pub entry: Vec<AtomEntry>,
+impl AtomEntry {
+    fn from_element(entry: &Element) -> Self {
+        // Atom 0.3 called these issued and modified
+        let date = |names: &[&str]| names.iter()
+            .find_map(|name| entry.child_text(name))
+            .map(FeedDate::from);
+
+        let enclosures = entry.children("link")
+            .filter(|link| link.attribute("rel") == Some("enclosure"))
+            .filter_map(|link| enclosure_from(link, "type", "length"))
+            .collect();
+
+        let categories = entry.children("category")
+            .filter_map(|category| category.attribute("label").or(category.attribute("term")))
+            .map(|category| category.trim().to_string())
+            .filter(|category| !category.is_empty());
+
+        Self {
+            base: entry.base.clone(),
+            id: entry.child_text("id"),
+            link: link(entry),
+            updated: date(&["updated", "modified"]),
+            published: date(&["published", "issued"]),
+            // Title is HTML like summary and content, flattened to text once sanitized
+            title: entry.child("title").and_then(html),
+            summary: entry.child("summary").and_then(html),
+            content: entry.child("content").and_then(html),
+            authors: authors(entry),
+            categories: distinct(categories),
+            replies: entry.children("link")
+                .filter_map(AtomLink::from_element)
+                .find(|link| link.rel.as_deref() == Some("replies") && link.is_html())
+                .map(|link| link.url()),
+            media: Media::from_element(entry, enclosures),
+        }
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct AtomFeed {
-    pub feed: AtomRoot,
+fn authors(element: &Element) -> Vec<String> {
+    distinct(element.children("author").filter_map(|author| author.child_text("name").or_else(|| author.child_text("email"))))
 }
 
-fn link<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum LinkMultiType {
-        Vec(Vec<AtomLink>),
-        Single(AtomLink),
+// Atom text constructs as HTML, whichever of text, html or xhtml they were written in
+fn html(element: &Element) -> Option<String> {
+    match element.attribute("type").unwrap_or("text") {
+        "html" | "text/html" => element.text(),
+        "text" | "text/plain" => element.text().map(|text| escape(text.as_str()).into_owned()),
+        "xhtml" | "application/xhtml+xml" => element.child("div")
+            .map(Element::inner_xml)
+            .map(|xml| xml.trim().to_string())
+            .filter(|xml| !xml.is_empty()),
+        // Other media types, or content that only links out with src
+        _ => None,
     }
+}
 
-    match LinkMultiType::deserialize(deserializer)? {
-        LinkMultiType::Vec(v) => {
-          let link = v.iter().find(|link| link.link_type == "text/html").unwrap().href.to_string();
-          Ok(link)
-        },
-        LinkMultiType::Single(link) => Ok(link.href)
-    }
+#[derive(Serialize, Debug)]
+pub struct AtomFeed {
+    pub title: Option<String>,
+    // The site the feed belongs to
+    pub link: Option<String>,
+    pub subtitle: Option<String>,
+    // <icon>, or the larger <logo> when there's no icon
+    pub icon: Option<String>,
+    pub entry: Vec<AtomEntry>,
+    // Problems with the document itself, like markup that broke partway through
+    pub warnings: Vec<String>,
 }
 
-fn title<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum TitleMultiType {
-        Map(AtomTitle),
-        Single(String),
-    }
+fn link(entry: &Element) -> Option<String> {
+    // Prefer the alternate, then whatever link there is
+    alternate_link(entry).or_else(|| entry.children("link")
+        .find_map(AtomLink::from_element)
+        .map(|link| link.url()))
+}
 
-    match TitleMultiType::deserialize(deserializer)? {
-        TitleMultiType::Map(m) => Ok(m.title),
-        TitleMultiType::Single(title) => Ok(title)
-    }
+// The HTML alternate, then any alternate
+fn alternate_link(element: &Element) -> Option<String> {
+    let links: Vec<AtomLink> = element.children("link")
+        .filter_map(AtomLink::from_element)
+        .filter(AtomLink::is_alternate)
+        .collect();
+
+    links.iter().find(|link| link.is_html())
+        .or(links.first())
+        .map(AtomLink::url)
+}
+
+// Text of a child that holds a URL, resolved against the child's xml:base
+fn child_url(element: &Element, name: &str) -> Option<String> {
+    let child = element.child(name)?;
+    Some(resolve_xml_base(child.base.as_deref(), &child.text()?))
 }
 
-fn updated_date_time<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    // Atom Date: 2024-07-23T07:28:00+00:00
-    if let Ok(dt) = DateTime::parse_from_rfc3339(&s) {
-        return Ok(Some(dt.with_timezone(&Utc)));
+pub fn read_atom(xml: &str, feed_url: Option<&str>) -> Result<AtomFeed, AtomError> {
+    let mut entry = Vec::new();
+    let document = read_document(xml, feed_url, "entry", |element| entry.push(AtomEntry::from_element(&element)))?;
+
+    if document.root.name != "feed" {
+        return Err(AtomError::Message(format!("Expected <feed> but found <{}>", document.root.name)));
     }
 
-    Err(de::Error::custom(&format!(
-        "Failed to parse Atom date: {}",
-        &s
-    )))
-}
+    // Entries without an author of their own are credited to the feed's
+    let feed_authors = authors(&document.root);
+    for entry in entry.iter_mut().filter(|entry| entry.authors.is_empty()) {
+        entry.authors = feed_authors.clone();
+    }
 
-pub fn atom_to_json(value: Value) -> Result<AtomFeed, AtomError> {
-    from_value(value).map_err(|e| AtomError::Message(e.to_string()))
+    Ok(AtomFeed {
+        title: document.root.child("title").and_then(html),
+        // Only an alternate describes the site, the feed's other links point at itself or its hub
+        link: alternate_link(&document.root),
+        subtitle: document.root.child("subtitle").and_then(html),
+        icon: child_url(&document.root, "icon").or_else(|| child_url(&document.root, "logo")),
+        entry,
+        warnings: document.warnings,
+    })
 }
 
 #[cfg(test)]
 mod tests {
-  use serde_json::json;
   use super::*;
 
+  fn atom_with_entry(entry: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="utf-8"?>
+      <feed xmlns="http://www.w3.org/2005/Atom">
+        <title>Technical Grimoire</title>
+        <entry>{entry}</entry>
+      </feed>"#)
+  }
+
   fn assert_atom_feed_parsed(result: Result<AtomFeed, AtomError>) {
     match result {
       Ok(feed) => {
-        assert!(!feed.feed.entry.is_empty(), "Expected at least one entry");
+        assert!(!feed.entry.is_empty(), "Expected at least one entry");
 
-        let first_entry = &feed.feed.entry[0];
-        assert_eq!(first_entry.link, "https://technicalgrimoire.com/david/2024/10/keyburg-videogame");
-        assert_eq!(first_entry.title, "I Made a Terrible Video Game");
-        assert_eq!(first_entry.updated.unwrap().to_string(), "2024-10-09 18:55:25 UTC");
+        let first_entry = &feed.entry[0];
+        assert_eq!(first_entry.link.as_deref(), Some("https://technicalgrimoire.com/david/2024/10/keyburg-videogame"));
+        assert_eq!(first_entry.title.as_deref(), Some("I Made a Terrible Video Game"));
+        assert_eq!(first_entry.updated.as_ref().and_then(FeedDate::parsed).unwrap().to_string(), "2024-10-09 18:55:25 UTC");
       }
       Err(e) => panic!("Parsing failed: {:?}", e),
     }
   }
 
   #[test]
-  fn test_atom_to_json_no_published() {
-    let data = json!({
-      "feed": {
-        "entry": [
-          {
-            "link": {
-              "@href": "https://technicalgrimoire.com/david/2024/10/keyburg-videogame",
-              "@rel": "alternate",
-              "@title": "I Made a Terrible Video Game",
-              "@type": "text/html"
-            },
-            "title": {
-              "#text": "I Made a Terrible Video Game",
-              "@type": "html"
-            },
-            "updated": "2024-10-09T18:55:25+00:00"
-          }
-        ],
-      }
-    });
+  fn test_read_atom_no_published() {
+    let xml = atom_with_entry(r#"
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" rel="alternate" title="I Made a Terrible Video Game" type="text/html" />
+      <title type="html">I Made a Terrible Video Game</title>
+      <updated>2024-10-09T18:55:25+00:00</updated>"#);
 
-    let result = atom_to_json(data);
+    let result = read_atom(&xml, None);
     assert_atom_feed_parsed(result);
   }
 
   #[test]
-  fn test_atom_to_json_title_map() {
-    let data = json!({
-      "feed": {
-        "entry": [
-          {
-            "link": {
-              "@href": "https://technicalgrimoire.com/david/2024/10/keyburg-videogame",
-              "@rel": "alternate",
-              "@title": "I Made a Terrible Video Game",
-              "@type": "text/html"
-            },
-            "title": {
-              "#text": "I Made a Terrible Video Game",
-              "@type": "html"
-            },
-            "published": "2024-09-01T00:00:00+00:00",
-            "updated": "2024-10-09T18:55:25+00:00"
-          }
-        ],
-      }
-    });
+  fn test_read_atom_title_attributes() {
+    let xml = atom_with_entry(r#"
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" rel="alternate" type="text/html" />
+      <title type="html">I Made a Terrible Video Game</title>
+      <published>2024-09-01T00:00:00+00:00</published>
+      <updated>2024-10-09T18:55:25+00:00</updated>"#);
 
-    let result = atom_to_json(data);
+    let result = read_atom(&xml, None);
     assert_atom_feed_parsed(result);
   }
 
   #[test]
-  fn test_atom_to_json_title_string() {
-    let data = json!({
-      "feed": {
-        "entry": [
-          {
-            "link": {
-              "@href": "https://technicalgrimoire.com/david/2024/10/keyburg-videogame",
-              "@rel": "alternate",
-              "@title": "I Made a Terrible Video Game",
-              "@type": "text/html"
-            },
-            "title": "I Made a Terrible Video Game",
-            "published": "2024-09-01T00:00:00+00:00",
-            "updated": "2024-10-09T18:55:25+00:00"
-          }
-        ],
-      }
-    });
+  fn test_read_atom_title_xhtml() {
+    let xml = atom_with_entry(r#"
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" />
+      <title type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml">I Made a <em>Terrible</em> Video Game</div></title>
+      <updated>2024-10-09T18:55:25+00:00</updated>"#);
+
+    let feed = read_atom(&xml, None).unwrap();
+    assert_eq!(feed.entry[0].title.as_deref(), Some("I Made a <em>Terrible</em> Video Game"));
+  }
 
-    let result = atom_to_json(data);
+  #[test]
+  fn test_read_atom_single_link() {
+    let xml = atom_with_entry(r#"
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" rel="alternate" type="text/html" />
+      <title>I Made a Terrible Video Game</title>
+      <published>2024-09-01T00:00:00+00:00</published>
+      <updated>2024-10-09T18:55:25+00:00</updated>"#);
+
+    let result = read_atom(&xml, None);
     assert_atom_feed_parsed(result);
   }
 
   #[test]
-  fn test_atom_to_json_link_map() {
-    let data = json!({
-      "feed": {
-        "entry": [
-          {
-            "link": {
-              "@href": "https://technicalgrimoire.com/david/2024/10/keyburg-videogame",
-              "@rel": "alternate",
-              "@title": "I Made a Terrible Video Game",
-              "@type": "text/html"
-            },
-            "title": "I Made a Terrible Video Game",
-            "published": "2024-09-01T00:00:00+00:00",
-            "updated": "2024-10-09T18:55:25+00:00"
-          }
-        ],
-      }
-    });
+  fn test_read_atom_prefers_alternate_link() {
+    let xml = atom_with_entry(r#"
+      <link href="https://technicalgrimoire.com/feed.atom" rel="self" type="application/atom+xml" />
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame.json" rel="alternate" type="application/json" />
+      <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" />
+      <title>I Made a Terrible Video Game</title>
+      <updated>2024-10-09T18:55:25+00:00</updated>"#);
 
-    let result = atom_to_json(data);
+    let result = read_atom(&xml, None);
     assert_atom_feed_parsed(result);
   }
 
   #[test]
-  fn test_atom_to_json_link_vec() {
-    let data = json!({
-      "feed": {
-        "entry": [
-          {
-            "link": [
-              {
-                "@href": "https://technicalgrimoire.com/david/2024/10/keyburg-videogame",
-                "@rel": "alternate",
-                "@title": "I Made a Terrible Video Game",
-                "@type": "text/html"
-              }
-            ],
-            "title": "I Made a Terrible Video Game",
-            "published": "2024-09-01T00:00:00+00:00",
-            "updated": "2024-10-09T18:55:25+00:00"
-          }
-        ],
-      }
-    });
+  fn test_read_atom_partial_entries() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+        <entry>
+          <link href="https://technicalgrimoire.com/feed.atom" rel="self" type="application/atom+xml" />
+          <updated>2024-10-09T18:55:25+00:00</updated>
+        </entry>
+        <entry>
+          <title>No link at all</title>
+        </entry>
+        <entry>
+          <link rel="alternate" />
+          <title>Link without href</title>
+        </entry>
+      </feed>"#;
+
+    let feed = read_atom(xml, None).unwrap();
+    assert_eq!(feed.entry[0].link.as_deref(), Some("https://technicalgrimoire.com/feed.atom"));
+    assert_eq!(feed.entry[0].title, None);
+    assert_eq!(feed.entry[1].link, None);
+    assert_eq!(feed.entry[2].link, None);
+  }
+
+  #[test]
+  fn test_read_atom_details() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+        <author><name>Technical Grimoire</name></author>
+        <entry>
+          <link href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" />
+          <link rel="replies" type="text/html" href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame#comments" />
+          <summary>Fish &amp; chips &lt;3</summary>
+          <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>Hello <b>world</b></p></div></content>
+          <author><name>David</name><email>david@example.org</email></author>
+          <author><email>pepper@example.org</email></author>
+          <category term="games" label="Video Games" />
+          <category term="devlog" />
+        </entry>
+        <entry>
+          <link href="https://technicalgrimoire.com/david/2024/11/another" />
+          <content type="html">&lt;p&gt;Escaped&lt;/p&gt;</content>
+        </entry>
+        <entry>
+          <link href="https://technicalgrimoire.com/david/2024/12/elsewhere" />
+          <content src="https://technicalgrimoire.com/david/2024/12/elsewhere.pdf" type="application/pdf" />
+        </entry>
+      </feed>"#;
+
+    let feed = read_atom(xml, None).unwrap();
+    let first_entry = &feed.entry[0];
+    assert_eq!(first_entry.summary.as_deref(), Some("Fish &amp; chips &lt;3"));
+    assert_eq!(first_entry.content.as_deref(), Some("<p>Hello <b>world</b></p>"));
+    assert_eq!(first_entry.authors, vec!["David".to_string(), "pepper@example.org".to_string()]);
+    assert_eq!(first_entry.categories, vec!["Video Games".to_string(), "devlog".to_string()]);
+    assert_eq!(first_entry.replies.as_deref(), Some("https://technicalgrimoire.com/david/2024/10/keyburg-videogame#comments"));
+    assert_eq!(feed.entry[1].content.as_deref(), Some("<p>Escaped</p>"));
+    assert_eq!(feed.entry[1].authors, vec!["Technical Grimoire".to_string()]);
+    assert_eq!(feed.entry[2].content, None);
+  }
+
+  #[test]
+  fn test_read_atom_media() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">
+        <entry>
+          <link rel="alternate" href="https://www.youtube.com/watch?v=abc123" />
+          <link rel="enclosure" type="video/mp4" length="1024" href="https://example.org/abc123.mp4" />
+          <media:group>
+            <media:title>I Made a Terrible Video Game</media:title>
+            <media:content url="https://www.youtube.com/v/abc123" type="application/x-shockwave-flash" duration="754" />
+            <media:thumbnail url="https://i.ytimg.com/vi/abc123/hqdefault.jpg" width="480" height="360" />
+          </media:group>
+        </entry>
+      </feed>"#;
+
+    let feed = read_atom(xml, None).unwrap();
+    let media = &feed.entry[0].media;
+    assert_eq!(media.enclosures.iter().map(|enclosure| enclosure.url.as_str()).collect::<Vec<_>>(),
+      vec!["https://example.org/abc123.mp4", "https://www.youtube.com/v/abc123"]);
+    assert_eq!(media.enclosures[0].length, Some(1024));
+    assert_eq!(media.duration, Some(754));
+    assert_eq!(media.thumbnails, vec!["https://i.ytimg.com/vi/abc123/hqdefault.jpg".to_string()]);
+  }
+
+  #[test]
+  fn test_read_atom_0_3() {
+    let xml = r#"<feed version="0.3" xmlns="http://purl.org/atom/ns#">
+        <entry>
+          <link rel="alternate" type="text/html" href="https://technicalgrimoire.com/david/2024/10/keyburg-videogame" />
+          <title>I Made a Terrible Video Game</title>
+          <modified>2024-10-09T18:55:25Z</modified>
+        </entry>
+      </feed>"#;
 
-    let result = atom_to_json(data);
+    let result = read_atom(xml, None);
     assert_atom_feed_parsed(result);
   }
 }
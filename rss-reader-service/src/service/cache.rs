@@ -1,10 +1,17 @@
-use std::{fmt, sync::Arc, time::Duration};
+use std::{fmt, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
 
+use futures::{stream, Stream, StreamExt};
+use chrono::Utc;
+use rand::Rng;
+use reqwest::Client;
+use shuttle_runtime::SecretStore;
 use sqlx::PgPool;
 use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
-use tokio::task;
+use tokio::{task, time::{sleep_until, Instant}};
 
-use crate::db::{CacheDataSource, CacheValue};
+use crate::db::{CacheDataSource, CacheValue, Feed, FeedDataSource, FetchLogDataSource, FetchLogInput};
+
+use super::{ingest_feed, FetchStats, FetchXmlError, IngestResult};
 
 #[derive(Debug)]
 pub enum CacheError {
@@ -42,7 +49,7 @@ pub async fn schedule_cache_clear(db: &PgPool) -> Result<(), JobSchedulerError>
     eprintln!("Failed to clear cache: {}", e);
   }
 
-  // Schedule cache clear every X minutes, deletes records greater than X minutes old
+  // Schedule cache clear every X minutes, deletes records of feeds that no longer exist
   sched.add(
     Job::new_repeated(Duration::from_secs(300), move |_uuid, _l| {
       println!("Running cache clear job");
@@ -59,4 +66,307 @@ pub async fn schedule_cache_clear(db: &PgPool) -> Result<(), JobSchedulerError>
   sched.start().await?;
 
   Ok(())
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RefreshConfig {
+  // How often the job looks for feeds that are due
+  pub tick: Duration,
+  // Default time between fetches of a single feed
+  pub interval: Duration,
+  // Upper bound for backed off intervals
+  pub max_interval: Duration,
+  // Unchanged fetches in a row before the interval starts doubling
+  pub backoff_threshold: i32,
+  pub jitter: Duration,
+  pub max_parallel: usize,
+  // Failed fetches in a row before a feed is disabled, 0 never disables
+  pub disable_after_failures: i32,
+  // Fetch log rows kept per feed, 0 keeps them all
+  pub fetch_log_retention: i64,
+  // Limits on every request to a publisher, so one that never answers can't stall the job
+  pub connect_timeout: Duration,
+  pub fetch_timeout: Duration,
+}
+
+impl RefreshConfig {
+  pub fn from_secrets(secrets: &SecretStore) -> Self {
+    fn secret_or(secrets: &SecretStore, key: &str, default: u64) -> u64 {
+      SecretStore::get(secrets, key)
+        .map(|value| value.parse().unwrap_or_else(|_| panic!("Invalid ENV_VAR: {key}={value}")))
+        .unwrap_or(default)
+    }
+
+    Self {
+      tick: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_TICK_SECS", 60)),
+      interval: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_INTERVAL_SECS", 600)),
+      max_interval: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_MAX_INTERVAL_SECS", 86400)),
+      backoff_threshold: secret_or(secrets, "FEED_REFRESH_BACKOFF_THRESHOLD", 3) as i32,
+      jitter: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_JITTER_SECS", 30)),
+      max_parallel: secret_or(secrets, "FEED_REFRESH_MAX_PARALLEL", 4).max(1) as usize,
+      disable_after_failures: secret_or(secrets, "FEED_DISABLE_AFTER_FAILURES", 10) as i32,
+      fetch_log_retention: secret_or(secrets, "FEED_FETCH_LOG_RETENTION", 100) as i64,
+      connect_timeout: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_CONNECT_TIMEOUT_SECS", 10)),
+      fetch_timeout: Duration::from_secs(secret_or(secrets, "FEED_REFRESH_TIMEOUT_SECS", 30)),
+    }
+  }
+
+  // Shared by everything that talks to publishers, so they all get the same limits
+  pub fn http_client(&self) -> Client {
+    Client::builder()
+      .connect_timeout(self.connect_timeout)
+      .timeout(self.fetch_timeout)
+      .build()
+      .unwrap_or_else(|e| panic!("Failed to build HTTP client: {}", e))
+  }
+}
+
+// Seconds until a feed should be fetched again. The base interval is the longer of the feed's
+// configured interval (or the default) and the publisher's hint, which then doubles for every
+// unchanged fetch past the backoff threshold.
+pub fn next_refresh_interval(
+  config: &RefreshConfig,
+  refresh_interval: Option<i32>,
+  refresh_hint: Option<i32>,
+  unchanged_fetches: i32,
+) -> i64 {
+  let configured = refresh_interval
+    .map(i64::from)
+    .unwrap_or(config.interval.as_secs() as i64);
+  let base = configured.max(refresh_hint.map(i64::from).unwrap_or(0)).max(1);
+
+  let backoff_steps = (unchanged_fetches - config.backoff_threshold + 1).clamp(0, 16);
+  let backed_off = base.saturating_mul(1 << backoff_steps);
+
+  // Never back off past the cap, but never poll more often than the base interval either
+  backed_off.min((config.max_interval.as_secs() as i64).max(base))
+}
+
+// Writes the attempt to the fetch log and updates the feed's health
+async fn record_fetch(
+  db: &PgPool,
+  config: &RefreshConfig,
+  feed: &Feed,
+  stats: &FetchStats,
+  result: &Result<IngestResult, FetchXmlError>
+) {
+  let fetch_log_db = FetchLogDataSource::new(db.clone());
+  let log = FetchLogInput {
+    feed_id: feed.id,
+    status_code: stats.status_code.map(i32::from),
+    error: result.as_ref().err().map(|e| e.kind().to_string()),
+    error_message: result.as_ref().err().map(|e| e.to_string()),
+    latency_ms: stats.latency.as_millis().min(i32::MAX as u128) as i32,
+    byte_count: stats.byte_count.map(|bytes| bytes.min(i32::MAX as usize) as i32),
+    warnings: result.as_ref().map(|ingested| ingested.warnings.clone()).unwrap_or_default(),
+  };
+  if let Err((status, err_msg)) = fetch_log_db.create_fetch_log(log).await {
+    eprintln!("Failed to write fetch log: {} - {} - {}", feed.name, status, err_msg);
+  }
+  if config.fetch_log_retention > 0 {
+    if let Err((status, err_msg)) = fetch_log_db.prune_fetch_logs(feed.id, config.fetch_log_retention).await {
+      eprintln!("Failed to prune fetch log: {} - {} - {}", feed.name, status, err_msg);
+    }
+  }
+
+  let feed_db = FeedDataSource::new(db.clone());
+  match result {
+    Ok(ingested) => {
+      let warning_count = ingested.warnings.len().min(i32::MAX as usize) as i32;
+      if let Err((status, err_msg)) = feed_db.record_fetch_success(feed.id, warning_count).await {
+        eprintln!("Failed to record fetch success: {} - {} - {}", feed.name, status, err_msg);
+      }
+    },
+    Err(_) => match feed_db.record_fetch_failure(feed.id, config.disable_after_failures).await {
+      Ok(true) => eprintln!("Disabled feed after {} failed fetches: {}", feed.consecutive_failures + 1, feed.name),
+      Ok(false) => (),
+      Err((status, err_msg)) => {
+        eprintln!("Failed to record fetch failure: {} - {} - {}", feed.name, status, err_msg);
+      }
+    }
+  }
+}
+
+async fn refresh_feeds(db: &PgPool, client: &Client, config: RefreshConfig) {
+  let feed_db = FeedDataSource::new(db.clone());
+  let feeds = match feed_db.get_due_feeds().await {
+    Ok(feeds) => feeds,
+    Err((status, err_msg)) => {
+      eprintln!("Failed to load feeds for refresh: {} - {}", status, err_msg);
+      return;
+    }
+  };
+
+  // Spread requests out so every publisher isn't hit at the same instant
+  staggered(feeds, config.jitter)
+    .map(|feed| refresh_feed(db, client, &config, feed))
+    .buffer_unordered(config.max_parallel)
+    .for_each(|_| async {})
+    .await;
+}
+
+// Hands out items at random offsets within the jitter window, earliest first. The wait happens
+// before an item reaches the concurrent work, so none of its slots is ever held by a sleep.
+fn staggered<T>(items: Vec<T>, jitter: Duration) -> impl Stream<Item = T> {
+  let start = Instant::now();
+  let jitter_ms = jitter.as_millis() as u64;
+  let mut rng = rand::thread_rng();
+  let mut offsets: Vec<(Duration, T)> = items.into_iter()
+    .map(|item| (Duration::from_millis(rng.gen_range(0..=jitter_ms)), item))
+    .collect();
+  offsets.sort_by_key(|(offset, _)| *offset);
+
+  stream::iter(offsets).then(move |(offset, item)| async move {
+    sleep_until(start + offset).await;
+    item
+  })
+}
+
+async fn refresh_feed(db: &PgPool, client: &Client, config: &RefreshConfig, feed: Feed) {
+  println!("Refreshing feed: {}", feed.name);
+  let (stats, result) = ingest_feed(client, feed.id, &feed.name, &feed.url, db.clone()).await;
+  record_fetch(db, config, &feed, &stats, &result).await;
+
+  let (refresh_hint, unchanged_fetches) = match result {
+    Ok(result) => (
+      result.refresh_hint.map(|hint| hint.min(i32::MAX as i64) as i32),
+      if result.changed { 0 } else { feed.unchanged_fetches + 1 }
+    ),
+    Err(err) => {
+      eprintln!("Failed to refresh feed: {} - {}", feed.name, err);
+      (feed.refresh_hint, feed.unchanged_fetches)
+    }
+  };
+
+  let interval = next_refresh_interval(config, feed.refresh_interval, refresh_hint, unchanged_fetches);
+  let next_fetch_at = Utc::now() + chrono::Duration::seconds(interval);
+  let feed_db = FeedDataSource::new(db.clone());
+  if let Err((status, err_msg)) = feed_db.schedule_next_fetch(feed.id, refresh_hint, unchanged_fetches, next_fetch_at).await {
+    eprintln!("Failed to schedule next fetch: {} - {} - {}", feed.name, status, err_msg);
+  }
+}
+
+// Clears the running flag when a refresh ends, even when it panics, so later ticks aren't skipped
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+  fn drop(&mut self) {
+    self.0.store(false, Ordering::SeqCst);
+  }
+}
+
+pub async fn schedule_feed_refresh(db: &PgPool, client: Client, config: RefreshConfig) -> Result<(), JobSchedulerError> {
+  let db = Arc::new(db.clone());
+  let running = Arc::new(AtomicBool::new(false));
+  let sched = JobScheduler::new().await?;
+
+  println!("Scheduling feed refresh job: {:?}", config);
+
+  let run_refresh = move |db: Arc<PgPool>, running: Arc<AtomicBool>| {
+    // Skip this tick if the previous refresh is still working through slow publishers
+    if running.swap(true, Ordering::SeqCst) {
+      println!("Feed refresh already running, skipping");
+      return;
+    }
+
+    let client = client.clone();
+    task::spawn(async move {
+      let _guard = RunningGuard(running);
+      refresh_feeds(&db, &client, config).await;
+    });
+  };
+
+  // Run feed refresh on startup, without blocking the service from coming up
+  run_refresh(Arc::clone(&db), Arc::clone(&running));
+
+  sched.add(
+    Job::new_repeated(config.tick, move |_uuid, _l| {
+      println!("Running feed refresh job");
+      run_refresh(Arc::clone(&db), Arc::clone(&running));
+    })?
+  ).await?;
+
+  sched.start().await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config() -> RefreshConfig {
+    RefreshConfig {
+      tick: Duration::from_secs(60),
+      interval: Duration::from_secs(600),
+      max_interval: Duration::from_secs(86400),
+      backoff_threshold: 3,
+      jitter: Duration::from_secs(0),
+      max_parallel: 1,
+      disable_after_failures: 10,
+      fetch_log_retention: 100,
+      connect_timeout: Duration::from_secs(10),
+      fetch_timeout: Duration::from_secs(30),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_running_guard_resets_after_panic() {
+    let running = Arc::new(AtomicBool::new(true));
+    let guard = RunningGuard(Arc::clone(&running));
+    let result = task::spawn(async move {
+      let _guard = guard;
+      panic!("refresh failed");
+    }).await;
+
+    assert!(result.is_err());
+    assert!(!running.load(Ordering::SeqCst));
+  }
+
+  // Four fetches of 20s through two slots take 40s, plus at most one jitter window of waiting
+  #[tokio::test(start_paused = true)]
+  async fn test_staggered_starts_hold_no_slot() {
+    let start = Instant::now();
+    let fetched: Vec<i32> = staggered(vec![1, 2, 3, 4], Duration::from_secs(10))
+      .map(|feed| async move {
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        feed
+      })
+      .buffer_unordered(2)
+      .collect()
+      .await;
+
+    assert_eq!(fetched.len(), 4);
+    assert!(start.elapsed() <= Duration::from_secs(50), "took {:?}", start.elapsed());
+  }
+
+  #[test]
+  fn test_next_refresh_interval_default() {
+    assert_eq!(next_refresh_interval(&config(), None, None, 0), 600);
+  }
+
+  #[test]
+  fn test_next_refresh_interval_configured() {
+    assert_eq!(next_refresh_interval(&config(), Some(3600), None, 0), 3600);
+  }
+
+  #[test]
+  fn test_next_refresh_interval_honors_longer_hint() {
+    assert_eq!(next_refresh_interval(&config(), None, Some(1800), 0), 1800);
+    assert_eq!(next_refresh_interval(&config(), Some(3600), Some(1800), 0), 3600);
+  }
+
+  #[test]
+  fn test_next_refresh_interval_backoff() {
+    assert_eq!(next_refresh_interval(&config(), None, None, 2), 600);
+    assert_eq!(next_refresh_interval(&config(), None, None, 3), 1200);
+    assert_eq!(next_refresh_interval(&config(), None, None, 4), 2400);
+  }
+
+  #[test]
+  fn test_next_refresh_interval_backoff_capped() {
+    assert_eq!(next_refresh_interval(&config(), None, None, 100), 86400);
+    // A configured interval above the cap is still respected
+    assert_eq!(next_refresh_interval(&config(), Some(172800), None, 100), 172800);
+  }
+}
@@ -0,0 +1,68 @@
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+
+use crate::{db::{CategoryDataSource, CategoryInput}, AppState};
+
+pub async fn get_categories(
+  State(state): State<AppState>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Fetching categories");
+
+  let category_db = CategoryDataSource::new(state.db);
+  match category_db.get_categories().await {
+    Ok(categories) => Ok(Json(categories)),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+pub async fn create_category(
+  State(state): State<AppState>,
+  Json(category): Json<CategoryInput>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let category_db = CategoryDataSource::new(state.db);
+  match category_db.create_category(category).await {
+    Ok(category) => Ok((StatusCode::CREATED, Json(category))),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+pub async fn rename_category(
+  State(state): State<AppState>,
+  Path(id): Path<i32>,
+  Json(category): Json<CategoryInput>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let category_db = CategoryDataSource::new(state.db);
+  match category_db.rename_category(id, category).await {
+    Ok(category) => Ok(Json(category)),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+pub async fn delete_category(
+  State(state): State<AppState>,
+  Path(id): Path<i32>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let category_db = CategoryDataSource::new(state.db);
+  match category_db.delete_category(id).await {
+    Ok(()) => Ok(StatusCode::OK),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MergeInput {
+  // Category that takes over the feeds
+  pub into: i32
+}
+
+pub async fn merge_categories(
+  State(state): State<AppState>,
+  Path(id): Path<i32>,
+  Json(input): Json<MergeInput>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let category_db = CategoryDataSource::new(state.db);
+  match category_db.merge_categories(id, input.into).await {
+    Ok(category) => Ok(Json(category)),
+    Err(e) => Err(e.into_response())
+  }
+}
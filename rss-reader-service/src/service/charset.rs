@@ -0,0 +1,163 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+#[derive(Debug, PartialEq)]
+pub struct DecodedBody {
+  pub text: String,
+  pub encoding: &'static str,
+  // Set when byte sequences invalid in the encoding had to be replaced
+  pub warning: Option<String>,
+}
+
+// Turns a fetched body into UTF-8. The encoding comes from a byte order mark, then the
+// Content-Type charset, then the XML declaration, and is UTF-8 when none of them say.
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> DecodedBody {
+  let (encoding, bom_length) = Encoding::for_bom(bytes)
+    .or_else(|| header_charset(content_type).map(|encoding| (encoding, 0)))
+    .or_else(|| declared_encoding(bytes).map(|encoding| (encoding, 0)))
+    .unwrap_or((UTF_8, 0));
+
+  let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+  DecodedBody {
+    text: text.into_owned(),
+    encoding: encoding.name(),
+    warning: had_errors.then(|| format!("Replaced bytes that aren't valid {}", encoding.name())),
+  }
+}
+
+fn header_charset(content_type: Option<&str>) -> Option<&'static Encoding> {
+  content_type?.split(';')
+    .skip(1)
+    .filter_map(|parameter| parameter.split_once('='))
+    .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+    .and_then(|(_, charset)| Encoding::for_label(charset.trim().trim_matches('"').as_bytes()))
+}
+
+// Reads encoding="..." from the <?xml ?> declaration. Without a BOM, UTF-16 shows itself
+// through the zero bytes around the declaration's opening.
+fn declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+  match bytes {
+    [b'<', 0, b'?', 0, ..] => return Some(UTF_16LE),
+    [0, b'<', 0, b'?', ..] => return Some(UTF_16BE),
+    _ => (),
+  }
+
+  let start = bytes.iter().position(|byte| !byte.is_ascii_whitespace())?;
+  let prolog = &bytes[start..];
+  if !prolog.starts_with(b"<?xml") {
+    return None;
+  }
+  let declaration = &prolog[..prolog.iter().position(|byte| *byte == b'>')?];
+  let declaration = std::str::from_utf8(declaration).ok()?;
+
+  let (_, rest) = declaration.split_once("encoding")?;
+  let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+  let quote = rest.chars().next().filter(|quote| *quote == '"' || *quote == '\'')?;
+  let (label, _) = rest[1..].split_once(quote)?;
+
+  // A document that names UTF-16 but decoded as bytes this far is really some ASCII superset
+  Encoding::for_label(label.trim().as_bytes())
+    .filter(|encoding| *encoding != UTF_16LE && *encoding != UTF_16BE)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The same channel saved in different encodings, each titled "Café – “Ünïcödé” €5"
+  const TITLE: &str = "Café – “Ünïcödé” €5";
+
+  fn title_of(decoded: &DecodedBody) -> &str {
+    let start = decoded.text.find("<title>").unwrap() + "<title>".len();
+    let end = decoded.text.find("</title>").unwrap();
+    &decoded.text[start..end]
+  }
+
+  #[test]
+  fn test_decode_utf8() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/utf-8.xml"), Some("application/rss+xml"));
+    assert_eq!(decoded.encoding, "UTF-8");
+    assert_eq!(title_of(&decoded), TITLE);
+    assert_eq!(decoded.warning, None);
+  }
+
+  #[test]
+  fn test_decode_utf8_bom() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/utf-8-bom.xml"), Some("text/xml; charset=iso-8859-1"));
+    assert_eq!(decoded.encoding, "UTF-8");
+    assert!(decoded.text.starts_with("<?xml"));
+    assert_eq!(title_of(&decoded), TITLE);
+  }
+
+  #[test]
+  fn test_decode_utf16_bom() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/utf-16le-bom.xml"), None);
+    assert_eq!(decoded.encoding, "UTF-16LE");
+    assert_eq!(title_of(&decoded), TITLE);
+
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/utf-16be-bom.xml"), None);
+    assert_eq!(decoded.encoding, "UTF-16BE");
+    assert_eq!(title_of(&decoded), TITLE);
+  }
+
+  #[test]
+  fn test_decode_utf16_without_bom() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/utf-16le.xml"), None);
+    assert_eq!(decoded.encoding, "UTF-16LE");
+    assert_eq!(title_of(&decoded), TITLE);
+  }
+
+  #[test]
+  fn test_decode_windows_1252_declaration() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/windows-1252.xml"), Some("application/rss+xml"));
+    assert_eq!(decoded.encoding, "windows-1252");
+    assert_eq!(title_of(&decoded), TITLE);
+    assert_eq!(decoded.warning, None);
+  }
+
+  #[test]
+  fn test_decode_iso_8859_1_as_windows_1252() {
+    // Publishers labelling Windows-1252 text as Latin-1 is common enough that browsers decode
+    // the label as Windows-1252, and so do we
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/iso-8859-1.xml"), None);
+    assert_eq!(decoded.encoding, "windows-1252");
+    assert_eq!(title_of(&decoded), "Café Ünïcödé");
+  }
+
+  #[test]
+  fn test_decode_header_charset() {
+    // No declaration, so only the header knows
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/koi8-r.xml"), Some("text/xml; charset=\"KOI8-R\""));
+    assert_eq!(decoded.encoding, "KOI8-R");
+    assert_eq!(title_of(&decoded), "Привет, мир");
+  }
+
+  #[test]
+  fn test_decode_header_overrides_declaration() {
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/shift_jis.xml"), Some("application/atom+xml; charset=Shift_JIS"));
+    assert_eq!(decoded.encoding, "Shift_JIS");
+    assert_eq!(title_of(&decoded), "こんにちは世界");
+
+    // The document's own declaration agrees when the header is missing
+    let decoded = decode_body(include_bytes!("../../fixtures/charset/shift_jis.xml"), Some("application/atom+xml"));
+    assert_eq!(title_of(&decoded), "こんにちは世界");
+  }
+
+  #[test]
+  fn test_decode_mislabeled_utf8() {
+    // Latin-1 bytes served without any label are replaced rather than rejected
+    let decoded = decode_body(b"<rss><channel><title>Caf\xe9</title></channel></rss>", None);
+    assert_eq!(decoded.encoding, "UTF-8");
+    assert_eq!(title_of(&decoded), "Caf\u{fffd}");
+    assert_eq!(decoded.warning.as_deref(), Some("Replaced bytes that aren't valid UTF-8"));
+  }
+
+  #[test]
+  fn test_declared_encoding() {
+    assert_eq!(declared_encoding(b"<?xml version='1.0' encoding='ISO-8859-15'?><rss/>"), Encoding::for_label(b"iso-8859-15"));
+    assert_eq!(declared_encoding(b"\n  <?xml version=\"1.0\" encoding = \"utf-8\" ?>"), Some(UTF_8));
+    assert_eq!(declared_encoding(b"<?xml version=\"1.0\"?><rss/>"), None);
+    assert_eq!(declared_encoding(b"<?xml version=\"1.0\" encoding=\"utf-16\"?>"), None);
+    assert_eq!(declared_encoding(b"<rss encoding=\"koi8-r\"/>"), None);
+    assert_eq!(declared_encoding(b"{\"version\": \"https://jsonfeed.org/version/1.1\"}"), None);
+  }
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+// A feed date that either parsed, or the raw text that couldn't be, so a bad date only costs
+// its own entry rather than failing the whole document
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum FeedDate {
+  Parsed(DateTime<Utc>),
+  Invalid(String),
+}
+
+impl FeedDate {
+  pub fn parsed(&self) -> Option<DateTime<Utc>> {
+    match self {
+      FeedDate::Parsed(dt) => Some(*dt),
+      FeedDate::Invalid(_) => None,
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for FeedDate {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Ok(match Value::deserialize(deserializer)? {
+      Value::String(s) => FeedDate::from(s),
+      other => FeedDate::from(other.to_string()),
+    })
+  }
+}
+
+impl From<String> for FeedDate {
+  fn from(raw: String) -> Self {
+    match parse_date(&raw) {
+      Some(dt) => FeedDate::Parsed(dt),
+      None => FeedDate::Invalid(raw),
+    }
+  }
+}
+
+// Offsets in minutes for zone names seen in the wild, beyond the handful RFC 822 defines.
+// IST is read as India Standard Time, which is far more common in feeds than Irish Standard Time.
+const TIMEZONES: &[(&str, i32)] = &[
+  ("UT", 0), ("UTC", 0), ("GMT", 0), ("Z", 0), ("WET", 0),
+  ("EST", -5 * 60), ("EDT", -4 * 60),
+  ("CST", -6 * 60), ("CDT", -5 * 60),
+  ("MST", -7 * 60), ("MDT", -6 * 60),
+  ("PST", -8 * 60), ("PDT", -7 * 60),
+  ("AKST", -9 * 60), ("AKDT", -8 * 60),
+  ("HST", -10 * 60),
+  ("AST", -4 * 60), ("ADT", -3 * 60),
+  ("NST", -(3 * 60 + 30)), ("NDT", -(2 * 60 + 30)),
+  ("WEST", 60), ("BST", 60),
+  ("CET", 60), ("CEST", 2 * 60), ("MET", 60), ("MEST", 2 * 60),
+  ("EET", 2 * 60), ("EEST", 3 * 60), ("MSK", 3 * 60),
+  ("IST", 5 * 60 + 30),
+  ("SGT", 8 * 60), ("HKT", 8 * 60), ("AWST", 8 * 60),
+  ("JST", 9 * 60), ("KST", 9 * 60),
+  ("ACST", 9 * 60 + 30), ("ACDT", 10 * 60 + 30),
+  ("AEST", 10 * 60), ("AEDT", 11 * 60),
+  ("NZST", 12 * 60), ("NZDT", 13 * 60),
+];
+
+const MONTHS: &[&str] = &[
+  "january", "february", "march", "april", "may", "june",
+  "july", "august", "september", "october", "november", "december",
+];
+
+const ZONED_FORMATS: &[&str] = &[
+  "%d %b %Y %H:%M:%S %z",      // Example: 9 Oct 2024 18:55:25 +0000
+  "%d %b %Y %H:%M %z",         // Example: 9 Oct 2024 18:55 +0000
+  "%Y-%m-%dT%H:%M:%S%.f%z",    // Example: 2024-10-09T18:55:25.123+0000
+  "%Y-%m-%dT%H:%M%z",          // Example: 2024-10-09T18:55+00:00
+  "%Y-%m-%d %H:%M:%S%.f%z",    // Example: 2024-10-09 18:55:25+00:00
+  "%Y-%m-%d %H:%M:%S%.f %z",   // Example: 2024-10-09 18:55:25 +0000
+  "%Y-%m-%d %H:%M %z",         // Example: 2024-10-09 18:55 +0000
+];
+
+// Without an offset the date is assumed to be UTC
+const NAIVE_FORMATS: &[&str] = &[
+  "%Y-%m-%dT%H:%M:%S%.f",      // Example: 2024-10-09T18:55:25
+  "%Y-%m-%dT%H:%M",            // Example: 2024-10-09T18:55
+  "%Y-%m-%d %H:%M:%S%.f",      // Example: 2024-10-09 18:55:25
+  "%Y-%m-%d %H:%M",            // Example: 2024-10-09 18:55
+  "%d %b %Y %H:%M:%S",         // Example: 9 Oct 2024 18:55:25
+  "%d %b %Y %H:%M",            // Example: 9 Oct 2024 18:55
+];
+
+const DATE_FORMATS: &[&str] = &[
+  "%Y-%m-%d",                  // Example: 2024-10-09
+  "%d %b %Y",                  // Example: 9 Oct 2024
+  "%b %d, %Y",                 // Example: Oct 9, 2024
+  "%Y%m%d",                    // Example: 20241009
+];
+
+fn timezone_offset(zone: &str) -> Option<String> {
+  let zone = zone.trim_matches(|c| c == '(' || c == ')').to_ascii_uppercase();
+  TIMEZONES.iter()
+    .find(|(name, _)| *name == zone)
+    .map(|(_, minutes)| {
+      let sign = if *minutes < 0 { '-' } else { '+' };
+      format!("{}{:02}{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+    })
+}
+
+fn month_abbreviation(token: &str) -> Option<String> {
+  let word = token.trim_end_matches([',', '.']).to_ascii_lowercase();
+  if word.len() < 3 || !word.chars().all(|c| c.is_ascii_alphabetic()) {
+    return None;
+  }
+
+  MONTHS.iter()
+    .find(|month| month.starts_with(&word))
+    .map(|month| {
+      let abbreviation = &month[..3];
+      // Keep the comma of "October 9, 2024" style dates
+      if token.ends_with(',') { format!("{abbreviation},") } else { abbreviation.to_string() }
+    })
+}
+
+// Rewrites a date into the shapes the format tables expect: no weekday, abbreviated month
+// names and a numeric offset in place of a zone name
+fn normalize(s: &str) -> String {
+  let without_weekday = match s.split_once(',') {
+    Some((weekday, rest)) if weekday.chars().all(|c| c.is_ascii_alphabetic()) => rest,
+    _ => s,
+  };
+
+  let tokens: Vec<&str> = without_weekday.split_whitespace().collect();
+  tokens.iter().enumerate().map(|(i, token)| {
+    // ISO 8601 times glue the UTC designator straight on: 2024-10-09T18:55Z
+    if let Some(time) = token.strip_suffix(['Z', 'z']).filter(|time| time.ends_with(|c: char| c.is_ascii_digit())) {
+      return format!("{time}+0000");
+    }
+    if i == tokens.len() - 1 && i > 0 {
+      if let Some(offset) = timezone_offset(token) {
+        return offset;
+      }
+    }
+    month_abbreviation(token).unwrap_or_else(|| token.to_string())
+  }).collect::<Vec<_>>().join(" ")
+}
+
+pub fn parse_date(input: &str) -> Option<DateTime<Utc>> {
+  let s = input.trim();
+  if s.is_empty() {
+    return None;
+  }
+
+  if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+    return Some(dt.with_timezone(&Utc));
+  }
+  if let Ok(dt) = DateTime::parse_from_rfc2822(s) {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  let normalized = normalize(s);
+  if let Ok(dt) = DateTime::parse_from_rfc2822(&normalized) {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  for format in ZONED_FORMATS {
+    if let Ok(dt) = DateTime::parse_from_str(&normalized, format) {
+      return Some(dt.with_timezone(&Utc));
+    }
+  }
+
+  for format in NAIVE_FORMATS {
+    if let Ok(dt) = NaiveDateTime::parse_from_str(&normalized, format) {
+      return Some(dt.and_utc());
+    }
+  }
+
+  for format in DATE_FORMATS {
+    if let Ok(date) = NaiveDate::parse_from_str(&normalized, format) {
+      return date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use super::*;
+
+  const CASES: &[(&str, &str)] = &[
+    // RFC 822 / 2822
+    ("Wed, 11 Sep 2024 00:00:00 -0400", "2024-09-11T04:00:00+00:00"),
+    ("Tue, 03 Sep 2024 13:51:48 GMT", "2024-09-03T13:51:48+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 EST", "2024-10-09T23:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 PDT", "2024-10-10T01:55:25+00:00"),
+    ("Wed, 9 Oct 2024 18:55:25 +0000", "2024-10-09T18:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55 +0000", "2024-10-09T18:55:00+00:00"),
+    ("09 Oct 2024 18:55:25 +0000", "2024-10-09T18:55:25+00:00"),
+    ("Wed, 09 Oct 24 18:55:25 GMT", "2024-10-09T18:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 Z", "2024-10-09T18:55:25+00:00"),
+    ("wed, 09 oct 2024 18:55:25 +0000", "2024-10-09T18:55:25+00:00"),
+    // Named zones and spellings RFC 2822 doesn't accept
+    ("Wed, 09 Oct 2024 18:55:25 UTC", "2024-10-09T18:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 CEST", "2024-10-09T16:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 AEDT", "2024-10-09T07:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 IST", "2024-10-09T13:25:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25 +00:00", "2024-10-09T18:55:25+00:00"),
+    ("Wednesday, 09 October 2024 18:55:25 +0000", "2024-10-09T18:55:25+00:00"),
+    ("Wed, 09 Oct 2024 18:55:25", "2024-10-09T18:55:25+00:00"),
+    ("9 October 2024 18:55 BST", "2024-10-09T17:55:00+00:00"),
+    // RFC 3339 / ISO 8601
+    ("2024-10-09T18:55:25+00:00", "2024-10-09T18:55:25+00:00"),
+    ("2024-10-09T18:55:25Z", "2024-10-09T18:55:25+00:00"),
+    ("2024-10-09T18:55:25.123-07:00", "2024-10-10T01:55:25.123+00:00"),
+    ("2024-10-09T18:55:25+0200", "2024-10-09T16:55:25+00:00"),
+    ("2024-10-09T18:55Z", "2024-10-09T18:55:00+00:00"),
+    ("2024-10-09T18:55:25", "2024-10-09T18:55:25+00:00"),
+    ("2024-10-09 18:55:25", "2024-10-09T18:55:25+00:00"),
+    ("2024-10-09 18:55:25 +0200", "2024-10-09T16:55:25+00:00"),
+    // Bare dates
+    ("2024-10-09", "2024-10-09T00:00:00+00:00"),
+    ("9 Oct 2024", "2024-10-09T00:00:00+00:00"),
+    ("October 9, 2024", "2024-10-09T00:00:00+00:00"),
+    ("20241009", "2024-10-09T00:00:00+00:00"),
+    ("  2024-10-09T18:55:25Z\n", "2024-10-09T18:55:25+00:00"),
+  ];
+
+  #[test]
+  fn test_parse_date() {
+    for (input, expected) in CASES {
+      let parsed = parse_date(input)
+        .unwrap_or_else(|| panic!("Failed to parse date: {:?}", input));
+      assert_eq!(parsed.to_rfc3339(), *expected, "Parsing {:?}", input);
+    }
+  }
+
+  #[test]
+  fn test_parse_date_invalid() {
+    for input in ["", "yesterday", "Wed, 32 Oct 2024 18:55:25 GMT", "2024-13-09", "Oct 2024"] {
+      assert_eq!(parse_date(input), None, "Parsing {:?}", input);
+    }
+  }
+
+  #[test]
+  fn test_feed_date_deserialize() {
+    let parsed: FeedDate = serde_json::from_value(json!("Wed, 09 Oct 2024 18:55:25 EST")).unwrap();
+    assert_eq!(parsed.parsed().unwrap().to_rfc3339(), "2024-10-09T23:55:25+00:00");
+
+    let invalid: FeedDate = serde_json::from_value(json!("sometime last week")).unwrap();
+    assert_eq!(invalid, FeedDate::Invalid("sometime last week".to_string()));
+
+    let number: FeedDate = serde_json::from_value(json!(20241009)).unwrap();
+    assert_eq!(number.parsed().unwrap().to_rfc3339(), "2024-10-09T00:00:00+00:00");
+  }
+}
@@ -0,0 +1,315 @@
+use futures::future::join_all;
+use reqwest::{header, Client};
+use serde::Serialize;
+use url::Url;
+
+use super::{charset::decode_body, detect_feed_format, parse_feed_xml, FeedFormat, FetchXmlError};
+
+// Where sites commonly keep their feed when the page doesn't link to it
+const COMMON_PATHS: &[&str] = &["/feed", "/rss.xml", "/atom.xml", "/feed.xml", "/index.xml", "/feed.json"];
+
+const FEED_TYPES: &[(&str, FeedFormat)] = &[
+  ("application/rss+xml", FeedFormat::Rss),
+  ("application/atom+xml", FeedFormat::Atom),
+  ("application/rdf+xml", FeedFormat::Rdf),
+  ("application/feed+json", FeedFormat::JsonFeed),
+];
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateSource {
+  // The URL was a feed already
+  Feed,
+  // A <link rel="alternate"> on the page
+  Link,
+  // One of the common feed paths, confirmed to hold a feed
+  Path,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FeedCandidate {
+  pub url: String,
+  pub title: Option<String>,
+  pub format: Option<FeedFormat>,
+  pub source: CandidateSource,
+}
+
+struct Page {
+  url: Url,
+  content_type: Option<String>,
+  body: String,
+}
+
+async fn fetch_page(client: &Client, url: &str) -> Result<Page, FetchXmlError> {
+  let response = client.get(url).send().await?;
+  if !response.status().is_success() {
+    return Err(FetchXmlError::Http(response.status()));
+  }
+
+  let url = response.url().clone();
+  let content_type = response.headers()
+    .get(header::CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string());
+  let bytes = response.bytes().await?;
+  let body = decode_body(&bytes, content_type.as_deref()).text;
+
+  Ok(Page { url, content_type, body })
+}
+
+// The page's format when it parses as a feed
+fn feed_format(page: &Page) -> Option<FeedFormat> {
+  let format = detect_feed_format(&page.body, page.content_type.as_deref())?;
+  parse_feed_xml(&page.body, page.content_type.as_deref(), Some(page.url.as_str())).ok()?;
+  Some(format)
+}
+
+// Finds the feeds a site offers: the URL itself when it's a feed, the feeds its HTML links to,
+// or failing both, whichever common feed paths turn out to hold one
+pub async fn discover_feeds(client: &Client, url: &str) -> Result<Vec<FeedCandidate>, FetchXmlError> {
+  let page = fetch_page(client, url).await?;
+
+  if let Some(format) = feed_format(&page) {
+    return Ok(vec![FeedCandidate { url: page.url.to_string(), title: None, format: Some(format), source: CandidateSource::Feed }]);
+  }
+
+  let linked = linked_feeds(&page.body, &page.url);
+  if !linked.is_empty() {
+    return Ok(linked);
+  }
+
+  let probes = COMMON_PATHS.iter()
+    .filter_map(|path| page.url.join(path).ok())
+    .map(|candidate| {
+      let client = client.clone();
+      async move {
+        let page = fetch_page(&client, candidate.as_str()).await.ok()?;
+        let format = feed_format(&page)?;
+        Some(FeedCandidate { url: candidate.to_string(), title: None, format: Some(format), source: CandidateSource::Path })
+      }
+    });
+
+  Ok(join_all(probes).await.into_iter().flatten().collect())
+}
+
+// Feeds announced by <link rel="alternate"> tags, resolved against the page or its <base>
+fn linked_feeds(html: &str, page_url: &Url) -> Vec<FeedCandidate> {
+  let tags = tags(html);
+  let base = tags.iter()
+    .filter(|(name, _)| name == "base")
+    .find_map(|(_, attributes)| attribute(attributes, "href"))
+    .and_then(|href| page_url.join(&href).ok())
+    .unwrap_or_else(|| page_url.clone());
+
+  let mut candidates: Vec<FeedCandidate> = Vec::new();
+  for (_, attributes) in tags.iter().filter(|(name, _)| name == "link") {
+    let is_alternate = attribute(attributes, "rel")
+      .is_some_and(|rel| rel.split_ascii_whitespace().any(|rel| rel.eq_ignore_ascii_case("alternate")));
+    let format = attribute(attributes, "type").and_then(|link_type| {
+      let link_type = link_type.trim().to_ascii_lowercase();
+      FEED_TYPES.iter().find(|(feed_type, _)| *feed_type == link_type).map(|(_, format)| *format)
+    });
+    let url = attribute(attributes, "href").and_then(|href| base.join(href.trim()).ok());
+
+    if let (true, Some(format), Some(url)) = (is_alternate, format, url) {
+      if !candidates.iter().any(|candidate| candidate.url == url.as_str()) {
+        candidates.push(FeedCandidate {
+          url: url.to_string(),
+          title: attribute(attributes, "title").map(|title| title.trim().to_string()).filter(|title| !title.is_empty()),
+          format: Some(format),
+          source: CandidateSource::Link,
+        });
+      }
+    }
+  }
+  candidates
+}
+
+fn attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+  attributes.iter()
+    .find(|(key, _)| key == name)
+    .map(|(_, value)| value.clone())
+}
+
+// <link> and <base> tags with their attributes. Only the head matters for discovery, so this
+// stops there and skips comments rather than parsing the whole page as HTML.
+fn tags(html: &str) -> Vec<(String, Vec<(String, String)>)> {
+  let mut tags = Vec::new();
+  let mut rest = html;
+
+  while let Some(start) = rest.find('<') {
+    rest = &rest[start + 1..];
+    if let Some(comment) = rest.strip_prefix("!--") {
+      rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+      continue;
+    }
+
+    let name_length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '/').unwrap_or(rest.len());
+    let name = rest[..name_length].to_ascii_lowercase();
+    if name == "/head" || name == "body" {
+      break;
+    }
+
+    let (attributes, remaining) = attributes(&rest[name_length..]);
+    rest = remaining;
+    if name == "link" || name == "base" {
+      tags.push((name, attributes));
+    }
+  }
+
+  tags
+}
+
+// Attributes up to the end of a tag, and what follows it
+fn attributes(tag: &str) -> (Vec<(String, String)>, &str) {
+  let mut attributes = Vec::new();
+  let mut rest = tag;
+
+  loop {
+    rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    if rest.is_empty() {
+      return (attributes, rest);
+    }
+    if let Some(after) = rest.strip_prefix('>') {
+      return (attributes, after);
+    }
+
+    let name_length = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/').unwrap_or(rest.len()).max(1);
+    let name = rest[..name_length].to_ascii_lowercase();
+    rest = rest[name_length..].trim_start();
+
+    let value = match rest.strip_prefix('=') {
+      Some(value) => {
+        let value = value.trim_start();
+        let (value, remaining) = match value.chars().next() {
+          Some(quote @ ('"' | '\'')) => {
+            let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+            (&value[1..end], value.get(end + 1..).unwrap_or(""))
+          },
+          _ => {
+            let end = value.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(value.len());
+            (&value[..end], &value[end..])
+          },
+        };
+        rest = remaining;
+        unescape_attribute(value)
+      },
+      None => String::new(),
+    };
+    attributes.push((name, value));
+  }
+}
+
+fn unescape_attribute(value: &str) -> String {
+  value.replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use wiremock::{matchers::{method, path}, Mock, MockServer, ResponseTemplate};
+
+  use super::*;
+
+  const RSS: &str = r#"<?xml version="1.0"?><rss version="2.0"><channel><title>Blog</title>
+    <item><title>Hello</title><link>https://example.org/hello</link></item>
+  </channel></rss>"#;
+
+  #[test]
+  fn test_linked_feeds() {
+    let html = r#"<!DOCTYPE html>
+      <html><head>
+        <!-- <link rel="alternate" type="application/rss+xml" href="/old.xml"> -->
+        <link rel="stylesheet" href="/style.css">
+        <LINK REL="alternate" TYPE="application/rss+xml" TITLE="Posts &amp; notes" HREF="/feed.xml">
+        <link rel='alternate home' type='application/atom+xml' href='atom.xml' />
+        <link rel=alternate type=application/feed+json href=https://cdn.example.org/feed.json>
+        <link rel="alternate" type="text/html" hreflang="fr" href="/fr/">
+        <link rel="alternate" type="application/rss+xml" href="/feed.xml">
+      </head><body>
+        <link rel="alternate" type="application/rss+xml" href="/body.xml">
+      </body></html>"#;
+
+    let candidates = linked_feeds(html, &Url::parse("https://example.org/blog/").unwrap());
+    assert_eq!(candidates, vec![
+      FeedCandidate { url: "https://example.org/feed.xml".to_string(), title: Some("Posts & notes".to_string()), format: Some(FeedFormat::Rss), source: CandidateSource::Link },
+      FeedCandidate { url: "https://example.org/blog/atom.xml".to_string(), title: None, format: Some(FeedFormat::Atom), source: CandidateSource::Link },
+      FeedCandidate { url: "https://cdn.example.org/feed.json".to_string(), title: None, format: Some(FeedFormat::JsonFeed), source: CandidateSource::Link },
+    ]);
+  }
+
+  #[test]
+  fn test_linked_feeds_base() {
+    let html = r#"<head><base href="https://static.example.org/site/"><link rel="alternate" type="application/atom+xml" href="atom.xml"></head>"#;
+
+    let candidates = linked_feeds(html, &Url::parse("https://example.org/").unwrap());
+    assert_eq!(candidates[0].url, "https://static.example.org/site/atom.xml");
+  }
+
+  #[tokio::test]
+  async fn test_discover_feed_url() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200).insert_header("Content-Type", "application/rss+xml").set_body_string(RSS))
+      .mount(&server)
+      .await;
+
+    let candidates = discover_feeds(&Client::new(), &format!("{}/feed.xml", server.uri())).await.unwrap();
+    assert_eq!(candidates, vec![FeedCandidate {
+      url: format!("{}/feed.xml", server.uri()),
+      title: None,
+      format: Some(FeedFormat::Rss),
+      source: CandidateSource::Feed,
+    }]);
+  }
+
+  #[tokio::test]
+  async fn test_discover_linked_feeds() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("Content-Type", "text/html; charset=utf-8")
+        .set_body_string(r#"<html><head><link rel="alternate" type="application/rss+xml" title="Blog" href="/posts.rss"></head></html>"#))
+      .mount(&server)
+      .await;
+
+    let candidates = discover_feeds(&Client::new(), &format!("{}/", server.uri())).await.unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].url, format!("{}/posts.rss", server.uri()));
+    assert_eq!(candidates[0].title.as_deref(), Some("Blog"));
+  }
+
+  #[tokio::test]
+  async fn test_discover_common_paths() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/"))
+      .respond_with(ResponseTemplate::new(200).insert_header("Content-Type", "text/html").set_body_string("<html><head></head></html>"))
+      .mount(&server)
+      .await;
+    Mock::given(method("GET"))
+      .and(path("/atom.xml"))
+      .respond_with(ResponseTemplate::new(200).set_body_string(r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title></feed>"#))
+      .mount(&server)
+      .await;
+    // Soft 404s answer every path with HTML, which mustn't count as a feed
+    Mock::given(method("GET"))
+      .and(path("/feed"))
+      .respond_with(ResponseTemplate::new(200).insert_header("Content-Type", "text/html").set_body_string("<html>Not found</html>"))
+      .mount(&server)
+      .await;
+
+    let candidates = discover_feeds(&Client::new(), &server.uri()).await.unwrap();
+    assert_eq!(candidates, vec![FeedCandidate {
+      url: format!("{}/atom.xml", server.uri()),
+      title: None,
+      format: Some(FeedFormat::Atom),
+      source: CandidateSource::Path,
+    }]);
+  }
+}
@@ -1,14 +1,17 @@
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
-use axum::{extract::{Path, Query, State}, response::IntoResponse, Json};
+use axum::{extract::{Path, Query, State}, http::{header, StatusCode}, response::{IntoResponse, Response}, Json};
 use chrono::{DateTime, Utc};
 use futures::future::join_all;
+use reqwest::Client;
+use quick_xml::escape::escape;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use url::Url;
 
-use crate::{db::{FeedDataSource, FeedInput}, service::AtomEntry, AppState};
+use crate::{db::{self, Enclosure, EntryDataSource, EntryFilter, EntryInput, FeedDataSource, FeedFilter, FeedInput, FeedUpdate, FetchLog, FetchLogDataSource}, AppState};
 
-use super::{atom_to_json, fetch_feed_json, rss_to_json};
+use super::{media::Media, json_feed_from_value, timeline_page, read_atom, read_rdf, read_rss, discover_feeds, fetch_and_parse_feed, deserialize_timestamp, read_opml, write_opml, Clock, Duration, FeedDate, FeedFormat, LinkResolver, OpmlOutline, Sanitizer, Sanitizers, SystemClock, TimelineParam, web_link};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub enum FeedError {
@@ -25,9 +28,75 @@ impl Display for FeedError {
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Entry {
+  // The entry's guid or id within its feed
+  pub id: String,
   pub title: String,
   pub url: String,
-  pub created_date: String
+  pub summary: Option<String>,
+  pub content_html: Option<String>,
+  // Plain text opening of the summary or content
+  pub preview: Option<String>,
+  pub authors: Vec<String>,
+  pub categories: Vec<String>,
+  pub comments_url: Option<String>,
+  pub created_date: String,
+  pub updated_date: Option<String>,
+  pub enclosures: Vec<Enclosure>,
+  // Episode length in seconds
+  pub duration: Option<i32>,
+  pub episode: Option<i32>,
+  pub season: Option<i32>,
+  pub image_url: Option<String>,
+  pub thumbnails: Vec<String>,
+}
+
+impl From<db::Entry> for Entry {
+  fn from(entry: db::Entry) -> Self {
+    Self {
+      id: entry.identity,
+      title: entry.title,
+      url: entry.url,
+      summary: entry.summary,
+      content_html: entry.content_html,
+      preview: entry.preview,
+      authors: entry.authors,
+      categories: entry.categories,
+      comments_url: entry.comments_url,
+      created_date: entry.created_date.to_rfc3339(),
+      updated_date: entry.updated_date.map(|date| date.to_rfc3339()),
+      enclosures: entry.enclosures.0,
+      duration: entry.duration,
+      episode: entry.episode,
+      season: entry.season,
+      image_url: entry.image_url,
+      thumbnails: entry.thumbnails,
+    }
+  }
+}
+
+// Entries that haven't been stored yet are dated now when their feed gave no date
+impl From<EntryInput> for Entry {
+  fn from(entry: EntryInput) -> Self {
+    Self {
+      id: entry.identity,
+      title: entry.title,
+      url: entry.url,
+      summary: entry.summary,
+      content_html: entry.content_html,
+      preview: entry.preview,
+      authors: entry.authors,
+      categories: entry.categories,
+      comments_url: entry.comments_url,
+      created_date: entry.created_date.unwrap_or_else(Utc::now).to_rfc3339(),
+      updated_date: entry.updated_date.map(|date| date.to_rfc3339()),
+      enclosures: entry.enclosures,
+      duration: entry.duration,
+      episode: entry.episode,
+      season: entry.season,
+      image_url: entry.image_url,
+      thumbnails: entry.thumbnails,
+    }
+  }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -38,78 +107,422 @@ pub struct Feed {
 }
 
 impl Feed {
-  pub fn try_from_rss(name: String, category: String, duration: Duration, max_entries: usize, value: Value) -> Result<Self, FeedError> {
-    let items = rss_to_json(value)
-      .map_err(|e| FeedError::Message(e.to_string()))?
-      .rss.channel.item;
-
-    let items_in_duration: Vec<_> = items.iter().filter(|item| duration.compare(item.pub_date)).collect();
-    let item_count = max_entries.min(items_in_duration.len());
-    let trimmed_items = &items_in_duration[..item_count];
-
-    Ok(Self {
+  pub fn from_entries(name: String, category: String, entries: Vec<db::Entry>) -> Self {
+    Self {
       name,
       category,
-      entries: trimmed_items.iter().map(|item| Entry {
-        title: item.title.to_string(),
-        url: item.link.to_string(),
-        created_date: item.pub_date.to_string()
-      }).collect()
-    })
+      entries: entries.into_iter().map(Entry::from).collect()
+    }
   }
+}
 
-  pub fn try_from_atom(name: String, category: String, duration: Duration, max_entries: usize, value: Value) -> Result<Self, FeedError> {
-    let items = atom_to_json(value)
-      .map_err(|e| FeedError::Message(e.to_string()))?
-      .feed.entry;
+#[derive(Debug)]
+pub struct ParsedFeed {
+  pub format: FeedFormat,
+  pub title: Option<String>,
+  // The site the feed belongs to
+  pub site_url: Option<String>,
+  pub description: Option<String>,
+  pub icon_url: Option<String>,
+  pub entries: Vec<EntryInput>,
+  // Publisher's suggested polling interval in seconds
+  pub refresh_hint: Option<i64>,
+  // Problems with individual entries that were tolerated rather than failing the feed
+  pub warnings: Vec<String>,
+}
 
-    fn date(entry: &AtomEntry) -> DateTime<Utc> {
-      entry.published
-          .or(entry.updated)
-          .unwrap_or(DateTime::UNIX_EPOCH)
+impl ParsedFeed {
+  pub fn metadata(&self) -> db::FeedMetadata {
+    db::FeedMetadata {
+      title: self.title.clone(),
+      site_url: self.site_url.clone(),
+      description: self.description.clone(),
+      icon_url: self.icon_url.clone(),
     }
+  }
+}
 
-    let items_in_duration: Vec<_> = items.iter().filter(|item| duration.compare(date(item))).collect();
-    let item_count = max_entries.min(items_in_duration.len());
-    let trimmed_items = &items_in_duration[..item_count];
+// Picks the first candidate date that parsed. An entry whose dates are all unreadable keeps
+// its place in the feed, falling back to the time it was first seen, and leaves a warning.
+fn entry_date(identity: &str, dates: &[Option<FeedDate>], warnings: &mut Vec<String>) -> Option<DateTime<Utc>> {
+  let present: Vec<&FeedDate> = dates.iter().filter_map(|date| date.as_ref()).collect();
 
-    Ok(Self {
-      name,
-      category,
-      entries: trimmed_items.iter().map(|item| Entry {
-        title: item.title.to_string(),
-        url: item.link.to_string(),
-        created_date: date(item).to_string()
-      }).collect()
-    })
+  if let Some(date) = present.iter().find_map(|date| date.parsed()) {
+    return Some(date);
+  }
+
+  if let Some(FeedDate::Invalid(raw)) = present.first() {
+    warnings.push(format!("Unparseable date for entry {identity}: {raw}"));
   }
+
+  None
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
-#[serde(rename_all = "lowercase")]
-pub enum Duration {
-  DAY,
-  WEEK,
-  MONTH,
-  YEAR
+// Keeps the entries that deserialized, leaving a warning for each one that didn't
+fn valid_items<T>(items: Vec<Result<T, String>>, warnings: &mut Vec<String>) -> Vec<T> {
+  items.into_iter().enumerate().filter_map(|(i, item)| match item {
+    Ok(item) => Some(item),
+    Err(e) => {
+      warnings.push(format!("Skipped entry at position {}: {}", i + 1, e));
+      None
+    }
+  }).collect()
 }
 
-impl Duration {
-  pub fn compare(&self, date: chrono::DateTime<Utc>) -> bool {
-    let now = Utc::now();
-    match self {
-      Duration::DAY => date >= now - chrono::Duration::days(1),
-      Duration::WEEK => date >= now - chrono::Duration::weeks(1),
-      Duration::MONTH => date >= now - chrono::Duration::weeks(4),
-      Duration::YEAR => date >= now - chrono::Duration::weeks(52),
+// Link for an entry that has none of its own, when its id happens to be a URL
+fn permalink(id: Option<&str>) -> Option<String> {
+  id.filter(|id| id.starts_with("http://") || id.starts_with("https://"))
+    .map(|id| id.to_string())
+}
+
+// Stand-in title for untitled entries, which are common in microblog style feeds
+fn fallback_title(text: &str) -> Option<String> {
+  let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+  (!text.is_empty()).then(|| text.chars().take(80).collect())
+}
+
+// What a channel says about itself, cleaned the same way as its entries
+struct ChannelText {
+  title: Option<String>,
+  site_url: Option<String>,
+  description: Option<String>,
+  icon_url: Option<String>,
+}
+
+impl ChannelText {
+  // Title and description are taken as HTML, and only web links are kept
+  fn sanitize(sanitizer: &Sanitizer, base: Option<&Url>, title: Option<&str>, link: Option<&str>, description: Option<&str>, icon: Option<&str>) -> Self {
+    Self {
+      title: title.and_then(|title| sanitizer.plain_text(title)),
+      site_url: link.and_then(|link| web_link(base, link)),
+      description: description.and_then(|description| sanitizer.plain_text(description)),
+      icon_url: icon.and_then(|icon| web_link(base, icon)),
     }
-  } 
+  }
 }
 
-#[derive(Deserialize, Debug)]
+// An entry's publisher markup, cleaned for display
+struct EntryText {
+  title: Option<String>,
+  summary: Option<String>,
+  content_html: Option<String>,
+  preview: Option<String>,
+}
+
+impl EntryText {
+  // Title, summary and content are all taken as HTML
+  fn sanitize(sanitizer: &Sanitizer, title: Option<&str>, summary: Option<&str>, content: Option<&str>) -> Self {
+    let body = summary.or(content);
+    Self {
+      title: title.and_then(|title| sanitizer.plain_text(title))
+        .or_else(|| body.and_then(|body| sanitizer.plain_text(body)).as_deref().and_then(fallback_title)),
+      summary: summary.and_then(|summary| sanitizer.safe_html(summary)),
+      content_html: content.and_then(|content| sanitizer.safe_html(content)),
+      preview: body.and_then(|body| sanitizer.preview(body)),
+    }
+  }
+}
+
+// An item as its format describes it, before its links are resolved and its markup cleaned
+struct RawEntry {
+  // xml:base in effect for the item
+  base: Option<String>,
+  // The guid, id or rdf:about
+  id: Option<String>,
+  // Links to the entry's page, best first
+  links: Vec<String>,
+  // Candidates for when the entry was published, best first
+  dates: Vec<Option<FeedDate>>,
+  updated: Option<FeedDate>,
+  // Title, summary and content are all HTML
+  title: Option<String>,
+  summary: Option<String>,
+  content: Option<String>,
+  authors: Vec<String>,
+  categories: Vec<String>,
+  comments: Option<String>,
+  media: Media,
+}
+
+// Turns one feed's items into entries, the same way whichever format they came in
+struct EntryBuilder {
+  resolver: LinkResolver,
+  sanitizers: Sanitizers,
+  warnings: Vec<String>,
+}
+
+impl EntryBuilder {
+  fn new(resolver: LinkResolver, warnings: Vec<String>) -> Self {
+    Self { resolver, sanitizers: Sanitizers::default(), warnings }
+  }
+
+  // The channel's own details, taken against the feed's base
+  fn channel_text(&mut self, title: Option<&str>, link: Option<&str>, description: Option<&str>, icon: Option<&str>) -> ChannelText {
+    let base = self.resolver.base(None);
+    ChannelText::sanitize(self.sanitizers.for_base(base.as_ref()), base.as_ref(), title, link, description, icon)
+  }
+
+  // Entries without a web link or a permalink id have nowhere for a reader to go, so they're left
+  // out with a warning naming them by id, or by unnamed when they have none
+  fn entry(&mut self, entry: RawEntry, unnamed: &str) -> Option<EntryInput> {
+    let base = self.resolver.base(entry.base.as_deref());
+    let Some(url) = entry.links.iter().find_map(|link| web_link(base.as_ref(), link)).or_else(|| permalink(entry.id.as_deref())) else {
+      self.warnings.push(format!("Skipped entry {}: missing link", entry.id.as_deref().unwrap_or(unnamed)));
+      return None;
+    };
+    let identity = entry.id.unwrap_or_else(|| url.clone());
+    let sanitizer = self.sanitizers.for_base(base.as_ref());
+    let text = EntryText::sanitize(sanitizer, entry.title.as_deref(), entry.summary.as_deref(), entry.content.as_deref());
+    let media = entry.media.resolve(base.as_ref());
+    Some(EntryInput {
+      created_date: entry_date(&identity, &entry.dates, &mut self.warnings),
+      updated_date: entry.updated.as_ref().and_then(FeedDate::parsed),
+      title: text.title.unwrap_or_else(|| url.clone()),
+      identity,
+      url,
+      summary: text.summary,
+      content_html: text.content_html,
+      preview: text.preview,
+      authors: entry.authors,
+      categories: entry.categories,
+      comments_url: entry.comments.and_then(|comments| web_link(base.as_ref(), &comments)),
+      enclosures: media.enclosures,
+      duration: media.duration,
+      episode: media.episode,
+      season: media.season,
+      image_url: media.image_url,
+      thumbnails: media.thumbnails,
+    })
+  }
+}
+
+pub fn feed_from_rss(xml: &str, feed_url: Option<&str>) -> Result<ParsedFeed, FeedError> {
+  let channel = read_rss(xml, feed_url)
+    .map_err(|e| FeedError::Message(e.to_string()))?;
+
+  let refresh_hint = channel.refresh_hint();
+  let mut builder = EntryBuilder::new(LinkResolver::new(feed_url, channel.link.as_deref()), channel.warnings);
+  let details = builder.channel_text(
+    channel.title.as_deref(),
+    channel.link.as_deref(),
+    channel.description.as_deref(),
+    channel.image.as_deref(),
+  );
+
+  let entries = channel.item.into_iter().filter_map(|item| builder.entry(RawEntry {
+    base: item.base,
+    id: item.guid,
+    links: item.link.into_iter().collect(),
+    dates: vec![item.pub_date],
+    updated: item.updated,
+    title: item.title,
+    summary: item.description,
+    content: item.content_encoded,
+    authors: item.authors,
+    categories: item.categories,
+    comments: item.comments,
+    media: item.media,
+  }, "without guid")).collect();
+
+  Ok(ParsedFeed {
+    format: FeedFormat::Rss,
+    title: details.title,
+    site_url: details.site_url,
+    description: details.description,
+    icon_url: details.icon_url,
+    refresh_hint,
+    entries,
+    warnings: builder.warnings
+  })
+}
+
+pub fn feed_from_atom(xml: &str, feed_url: Option<&str>) -> Result<ParsedFeed, FeedError> {
+  let feed = read_atom(xml, feed_url)
+    .map_err(|e| FeedError::Message(e.to_string()))?;
+
+  let mut builder = EntryBuilder::new(LinkResolver::new(feed_url, feed.link.as_deref()), feed.warnings);
+  let details = builder.channel_text(
+    feed.title.as_deref(),
+    feed.link.as_deref(),
+    feed.subtitle.as_deref(),
+    feed.icon.as_deref(),
+  );
+
+  let entries = feed.entry.into_iter().filter_map(|item| builder.entry(RawEntry {
+    base: item.base,
+    id: item.id,
+    links: item.link.into_iter().collect(),
+    dates: vec![item.published, item.updated.clone()],
+    updated: item.updated,
+    title: item.title,
+    summary: item.summary,
+    content: item.content,
+    authors: item.authors,
+    categories: item.categories,
+    comments: item.replies,
+    media: item.media,
+  }, "without id")).collect();
+
+  Ok(ParsedFeed {
+    format: FeedFormat::Atom,
+    title: details.title,
+    site_url: details.site_url,
+    description: details.description,
+    icon_url: details.icon_url,
+    refresh_hint: None,
+    entries,
+    warnings: builder.warnings
+  })
+}
+
+pub fn feed_from_rdf(xml: &str, feed_url: Option<&str>) -> Result<ParsedFeed, FeedError> {
+  let feed = read_rdf(xml, feed_url)
+    .map_err(|e| FeedError::Message(e.to_string()))?;
+
+  let refresh_hint = feed.channel.refresh_hint();
+  let mut builder = EntryBuilder::new(LinkResolver::new(feed_url, feed.channel.link.as_deref()), feed.warnings);
+  let details = builder.channel_text(
+    feed.channel.title.as_deref(),
+    feed.channel.link.as_deref(),
+    feed.channel.description.as_deref(),
+    feed.channel.image.as_deref(),
+  );
+
+  let entries = feed.item.into_iter().filter_map(|item| builder.entry(RawEntry {
+    base: item.base,
+    id: item.about,
+    links: item.link.into_iter().collect(),
+    dates: vec![item.date],
+    updated: item.modified,
+    title: item.title,
+    summary: item.description,
+    content: item.content_encoded,
+    authors: item.creators,
+    categories: item.subjects,
+    comments: None,
+    media: item.media,
+  }, "without rdf:about")).collect();
+
+  Ok(ParsedFeed {
+    format: FeedFormat::Rdf,
+    title: details.title,
+    site_url: details.site_url,
+    description: details.description,
+    icon_url: details.icon_url,
+    refresh_hint,
+    entries,
+    warnings: builder.warnings
+  })
+}
+
+pub fn feed_from_json_feed(value: Value, feed_url: Option<&str>) -> Result<ParsedFeed, FeedError> {
+  let feed = json_feed_from_value(value)
+    .map_err(|e| FeedError::Message(e.to_string()))?;
+
+  let mut warnings = Vec::new();
+  let items = valid_items(feed.items, &mut warnings);
+  let mut builder = EntryBuilder::new(LinkResolver::new(feed_url, feed.home_page_url.as_deref()), warnings);
+  // Title and description are plain text in JSON Feed
+  let plain_html = |text: &Option<String>| text.as_deref().map(|text| escape(text).into_owned());
+  let details = builder.channel_text(
+    plain_html(&feed.title).as_deref(),
+    feed.home_page_url.as_deref(),
+    plain_html(&feed.description).as_deref(),
+    feed.icon.as_deref().or(feed.favicon.as_deref()),
+  );
+
+  let entries = items.into_iter().filter_map(|item| {
+    let authors = item.authors.into_iter()
+      .chain(item.author)
+      .filter_map(|author| author.name.or(author.url));
+    let media = Media {
+      duration: item.attachments.iter()
+        .find_map(|attachment| attachment.duration_in_seconds)
+        .map(|duration| duration as i32),
+      enclosures: item.attachments.into_iter().map(|attachment| Enclosure {
+        url: attachment.url,
+        mime_type: Some(attachment.mime_type),
+        length: attachment.size_in_bytes.filter(|size| *size > 0),
+        duration: attachment.duration_in_seconds.map(|duration| duration as i32),
+      }).collect(),
+      image_url: item.image,
+      ..Media::default()
+    };
+
+    builder.entry(RawEntry {
+      base: None,
+      id: Some(item.id),
+      links: item.url.into_iter().chain(item.external_url).collect(),
+      dates: vec![item.date_published, item.date_modified.clone()],
+      updated: item.date_modified,
+      // Item titles and summaries are plain text too, and microblogs usually only carry content_text
+      title: plain_html(&item.title).or_else(|| plain_html(&item.summary)),
+      summary: plain_html(&item.summary),
+      content: item.content_html.or_else(|| plain_html(&item.content_text)),
+      authors: authors.collect(),
+      categories: item.tags,
+      comments: None,
+      media,
+    }, "without id")
+  }).collect();
+
+  Ok(ParsedFeed {
+    format: FeedFormat::JsonFeed,
+    title: details.title,
+    site_url: details.site_url,
+    description: details.description,
+    icon_url: details.icon_url,
+    refresh_hint: None,
+    entries,
+    warnings: builder.warnings
+  })
+}
+
+#[derive(Deserialize, Debug, Default)]
 pub struct FeedsParam {
+  // day, week, month, year or an ISO 8601 duration like P3D or PT6H
   pub duration: Option<Duration>,
-  pub max_entries: Option<usize>
+  pub max_entries: Option<usize>,
+  // Only entries with podcast or media attachments
+  pub has_enclosure: Option<bool>,
+  pub category: Option<String>,
+  pub feed_id: Option<i32>,
+  // Text to look for in entry titles and summaries
+  pub q: Option<String>,
+  // Overrides duration when set
+  #[serde(default, deserialize_with = "deserialize_timestamp")]
+  pub since: Option<DateTime<Utc>>,
+  // The window given by duration ends here rather than now
+  #[serde(default, deserialize_with = "deserialize_timestamp")]
+  pub until: Option<DateTime<Utc>>
+}
+
+impl FeedsParam {
+  pub fn feed_filter(&self) -> FeedFilter {
+    FeedFilter {
+      category: self.category.clone(),
+      feed_id: self.feed_id,
+    }
+  }
+
+  // Entries in the requested window, or in default_window when the request didn't ask for one
+  pub fn entry_filter(&self, default_window: Option<Duration>, clock: &impl Clock) -> EntryFilter {
+    let window = self.duration.or(default_window).map(|duration| match self.until {
+      Some(until) => duration.before(until),
+      None => duration.since(clock),
+    });
+    EntryFilter {
+      since: self.since.or(window),
+      until: self.until,
+      q: self.q.clone(),
+      has_enclosure: self.has_enclosure.unwrap_or(false),
+    }
+  }
+
+  // Feeds left without entries by a filter on entries are noise rather than an empty result
+  fn skips_empty_feeds(&self) -> bool {
+    self.has_enclosure.unwrap_or(false) || self.q.is_some()
+  }
 }
 
 pub async fn get_rss_feeds(
@@ -119,36 +532,34 @@ pub async fn get_rss_feeds(
   println!("Fetching all RSS feed data");
 
   let feed_db = FeedDataSource::new(state.db.clone());
-  let duration = params.duration.unwrap_or(Duration::WEEK);
-  let max_entries = params.max_entries.unwrap_or(5);
+  let max_entries = params.max_entries.unwrap_or(5) as i64;
+  let entry_filter = params.entry_filter(Some(Duration::WEEK), &SystemClock);
+  let skip_empty = params.skips_empty_feeds();
 
-  match feed_db.get_feeds().await {
+  match feed_db.get_filtered_feeds(&params.feed_filter()).await {
     Ok(feeds) => {
       let fetch_futures = feeds.into_iter().map(|feed| {
         println!("Preparing feed: {}", feed.name);
 
         let db = state.db.clone();
+        let entry_filter = &entry_filter;
         async move {
-          let result = fetch_feed_json(
-            &feed.name, 
-            &feed.category, 
-            &feed.url,
-            duration,
-            max_entries,
-            db
-          ).await;
-          (feed.name, result)
+          let entry_db = EntryDataSource::new(db);
+          let result = entry_db.get_entries(feed.id, entry_filter, max_entries).await;
+          (feed, result)
         }
       }).collect::<Vec<_>>();
 
       let results = join_all(fetch_futures).await;
 
       let mut values: Vec<Feed> = Vec::new();
-      results.into_iter().for_each(|(name, result)| {
+      results.into_iter().for_each(|(feed, result)| {
         match result {
-          Ok(feed) => values.push(feed),
+          // A podcast view has no use for feeds without any episodes
+          Ok(entries) if skip_empty && entries.is_empty() => {},
+          Ok(entries) => values.push(Feed::from_entries(feed.name, feed.category, entries)),
           Err(err) => {
-            println!("Failed to fetch feed: {} - {:?}", name, err)
+            println!("Failed to read entries: {} - {:?}", feed.name, err)
           }
         }
       });
@@ -171,10 +582,142 @@ pub async fn get_raw_feeds(
   }
 }
 
+#[derive(Serialize, Debug)]
+pub struct FeedHealth {
+  #[serde(flatten)]
+  pub feed: db::Feed,
+  pub recent_fetches: Vec<FetchLog>
+}
+
+pub async fn get_unhealthy_feeds(
+  State(state): State<AppState>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Fetching unhealthy feeds");
+
+  let feed_db = FeedDataSource::new(state.db.clone());
+  let feeds = feed_db.get_unhealthy_feeds().await
+    .map_err(|e| e.into_response())?;
+
+  let fetch_log_db = FetchLogDataSource::new(state.db);
+  let mut values: Vec<FeedHealth> = Vec::new();
+  for feed in feeds {
+    let recent_fetches = fetch_log_db.get_fetch_logs(feed.id, 10).await
+      .map_err(|e| e.into_response())?;
+    values.push(FeedHealth { feed, recent_fetches });
+  }
+
+  Ok::<_, Response>(Json(values))
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeedDetail {
+  #[serde(flatten)]
+  pub health: FeedHealth,
+  // Newest first, paged like /timeline
+  pub entries: Vec<Entry>,
+  // Absent on the last page
+  pub next_cursor: Option<String>,
+}
+
+pub async fn get_feed(
+  State(state): State<AppState>,
+  Path(id): Path<i32>,
+  Query(params): Query<TimelineParam>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Fetching feed: {}", id);
+
+  let feed_db = FeedDataSource::new(state.db.clone());
+  let feed = feed_db.get_feed(id).await
+    .map_err(|e| e.into_response())?;
+
+  let fetch_log_db = FetchLogDataSource::new(state.db.clone());
+  let recent_fetches = fetch_log_db.get_fetch_logs(id, 10).await
+    .map_err(|e| e.into_response())?;
+
+  let feed_filter = FeedFilter { feed_id: Some(id), ..FeedFilter::default() };
+  let (entries, next_cursor) = timeline_page(state.db, &feed_filter, &EntryFilter::default(), &params).await?;
+
+  Ok::<_, Response>(Json(FeedDetail {
+    health: FeedHealth { feed, recent_fetches },
+    entries: entries.into_iter().map(|timeline_entry| Entry::from(timeline_entry.entry)).collect(),
+    next_cursor,
+  }))
+}
+
+pub async fn enable_feed(
+  State(state): State<AppState>,
+  Path(id): Path<i32>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let feed_db = FeedDataSource::new(state.db);
+  match feed_db.enable_feed(id).await {
+    Ok(feed) => Ok(Json(feed)),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreviewParam {
+  pub max_entries: Option<usize>
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeedPreview {
+  pub name: String,
+  pub url: String,
+  pub format: FeedFormat,
+  pub title: Option<String>,
+  pub entry_count: usize,
+  pub entries: Vec<Entry>,
+  pub warnings: Vec<String>
+}
+
+pub async fn preview_feed(
+  State(state): State<AppState>,
+  Query(params): Query<PreviewParam>,
+  Json(feed): Json<FeedInput>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Previewing feed: {}", feed.url);
+
+  let max_entries = params.max_entries.unwrap_or(5);
+  match fetch_and_parse_feed(&state.client, &feed.url).await {
+    Ok(parsed) => Ok(Json(FeedPreview {
+      name: feed.name,
+      url: feed.url,
+      format: parsed.format,
+      title: parsed.title,
+      entry_count: parsed.entries.len(),
+      entries: parsed.entries.into_iter().take(max_entries).map(Entry::from).collect(),
+      warnings: parsed.warnings
+    })),
+    Err(e) => Err(e.into_response())
+  }
+}
+
+// Refuses feeds that can't be fetched and parsed, so typos don't get saved and fail on every fetch
+async fn check_feed(client: &Client, url: &str) -> Result<(), Response> {
+  fetch_and_parse_feed(client, url).await
+    .map(|_| ())
+    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Feed at {} could not be read: {}", url, e)).into_response())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchCreateParam {
+  // Save the feeds even when they can't be read right now
+  pub force: Option<bool>
+}
+
 pub async fn batch_create_feeds(
   State(state): State<AppState>,
+  Query(params): Query<BatchCreateParam>,
   Json(feeds): Json<Vec<FeedInput>>
 ) -> Result<impl IntoResponse, impl IntoResponse> {
+  if !params.force.unwrap_or(false) {
+    let checks = join_all(feeds.iter().map(|feed| check_feed(&state.client, &feed.url))).await;
+    if let Some(Err(e)) = checks.into_iter().find(|check| check.is_err()) {
+      return Err(e);
+    }
+  }
+
   let feed_db = FeedDataSource::new(state.db);
   match feed_db.batch_create_feeds(feeds).await {
     Ok(feeds) => Ok(Json(feeds)),
@@ -182,10 +725,117 @@ pub async fn batch_create_feeds(
   }
 }
 
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+  Created,
+  Skipped,
+  Failed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportResult {
+  #[serde(flatten)]
+  pub outline: OpmlOutline,
+  pub status: ImportStatus,
+  pub message: Option<String>
+}
+
+pub async fn import_opml(
+  State(state): State<AppState>,
+  Query(params): Query<BatchCreateParam>,
+  body: String
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let outlines = read_opml(&body)
+    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+  println!("Importing {} OPML outlines", outlines.len());
+
+  let feed_db = FeedDataSource::new(state.db);
+  let mut subscribed: HashSet<String> = feed_db.get_feeds().await
+    .map_err(|e| e.into_response())?
+    .into_iter()
+    .map(|feed| feed.url)
+    .collect();
+
+  // Fetching every feed takes a while, so they're all checked at once up front
+  let force = params.force.unwrap_or(false);
+  let checks = join_all(outlines.iter().map(|outline| async {
+    match &outline.url {
+      Some(url) if !force && !subscribed.contains(url) => fetch_and_parse_feed(&state.client, url).await.err(),
+      _ => None,
+    }
+  })).await;
+
+  let mut report: Vec<ImportResult> = Vec::new();
+  for (outline, check) in outlines.into_iter().zip(checks) {
+    let (status, message) = match (&outline.url, check) {
+      (None, _) => (ImportStatus::Skipped, Some("Outline has no xmlUrl".to_string())),
+      (Some(url), _) if subscribed.contains(url) => (ImportStatus::Skipped, Some("Already subscribed".to_string())),
+      (Some(url), Some(e)) => (ImportStatus::Failed, Some(format!("Feed at {} could not be read: {}", url, e))),
+      (Some(url), None) => {
+        let feed = FeedInput {
+          name: outline.name.clone(),
+          url: url.clone(),
+          category: outline.category.clone(),
+          refresh_interval: None
+        };
+        match feed_db.create_feed(feed).await {
+          Ok(_) => {
+            subscribed.insert(url.clone());
+            (ImportStatus::Created, None)
+          },
+          Err((_, e)) => (ImportStatus::Failed, Some(e)),
+        }
+      }
+    };
+    report.push(ImportResult { outline, status, message });
+  }
+
+  Ok::<_, Response>(Json(report))
+}
+
+pub async fn export_opml(
+  State(state): State<AppState>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Exporting feeds as OPML");
+
+  let feed_db = FeedDataSource::new(state.db);
+  let outlines: Vec<OpmlOutline> = feed_db.get_feeds().await
+    .map_err(|e| e.into_response())?
+    .into_iter()
+    .map(|feed| OpmlOutline { name: feed.name, url: Some(feed.url), category: feed.category })
+    .collect();
+
+  Ok::<_, Response>(([(header::CONTENT_TYPE, "text/x-opml; charset=utf-8")], write_opml(&outlines)))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateFeedParam {
+  // Look for the feed when the URL turns out to be a web page
+  pub discover: Option<bool>,
+  // Save the feed even when it can't be read right now
+  pub force: Option<bool>
+}
+
 pub async fn create_feed(
   State(state): State<AppState>,
-  Json(feed): Json<FeedInput>
+  Query(params): Query<CreateFeedParam>,
+  Json(mut feed): Json<FeedInput>
 ) -> Result<impl IntoResponse, impl IntoResponse> {
+  if params.discover.unwrap_or(false) {
+    let candidates = discover_feeds(&state.client, &feed.url).await
+      .map_err(|e| e.into_response())?;
+    let Some(candidate) = candidates.into_iter().next() else {
+      return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("No feeds found at {}", feed.url)).into_response());
+    };
+    println!("Discovered feed {} at {}", candidate.url, feed.url);
+    feed.url = candidate.url;
+  }
+
+  if !params.force.unwrap_or(false) {
+    check_feed(&state.client, &feed.url).await?;
+  }
+
   let feed_db = FeedDataSource::new(state.db);
   match feed_db.create_feed(feed).await {
     Ok(feeds) => Ok(Json(feeds)),
@@ -193,10 +843,114 @@ pub async fn create_feed(
   }
 }
 
+#[derive(Deserialize, Debug)]
+pub struct UpdateFeedParam {
+  // Save a new URL even when it can't be read right now
+  pub force: Option<bool>
+}
+
+pub async fn update_feed(
+  State(state): State<AppState>,
+  Path(id): Path<i32>,
+  Query(params): Query<UpdateFeedParam>,
+  Json(update): Json<FeedUpdate>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  let feed_db = FeedDataSource::new(state.db.clone());
+  let current = feed_db.get_feed(id).await
+    .map_err(|e| e.into_response())?;
+
+  let url_changed = update.url.as_ref().is_some_and(|url| *url != current.url);
+  if url_changed && !params.force.unwrap_or(false) {
+    check_feed(&state.client, update.url.as_deref().unwrap_or_default()).await?;
+  }
+
+  // The cache is moved or cleared in the same transaction as the update
+  let feed = feed_db.update_feed(id, update).await
+    .map_err(|e| e.into_response())?;
+
+  Ok::<_, Response>(Json(feed))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DiscoverInput {
+  pub url: String
+}
+
+pub async fn discover_feed(
+  State(state): State<AppState>,
+  Json(input): Json<DiscoverInput>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Discovering feeds at {}", input.url);
+
+  match discover_feeds(&state.client, &input.url).await {
+    Ok(candidates) => Ok(Json(candidates)),
+    Err(e) => Err(e.into_response())
+  }
+}
+
 pub async fn delete_feed(
   State(state): State<AppState>,
   Path(id): Path<i32>
 ) -> Result< impl IntoResponse, impl IntoResponse> {
   let feed_db = FeedDataSource::new(state.db);
   feed_db.delete_feed(id).await
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::Uri;
+  use chrono::TimeZone;
+
+  use crate::service::FixedClock;
+
+  use super::*;
+
+  fn params(query: &str) -> FeedsParam {
+    let uri: Uri = format!("/feeds?{query}").parse().unwrap();
+    Query::<FeedsParam>::try_from_uri(&uri).unwrap().0
+  }
+
+  #[test]
+  fn test_feeds_param_filters() {
+    let params = params("category=World%20News&feed_id=3&q=rust&since=2024-10-01T00:00:00Z&until=2024-10-08T12:00:00Z");
+    assert_eq!(params.feed_filter(), FeedFilter { category: Some("World News".to_string()), feed_id: Some(3) });
+    assert_eq!(params.entry_filter(Some(Duration::WEEK), &SystemClock), EntryFilter {
+      since: Some(Utc.with_ymd_and_hms(2024, 10, 1, 0, 0, 0).unwrap()),
+      until: Some(Utc.with_ymd_and_hms(2024, 10, 8, 12, 0, 0).unwrap()),
+      q: Some("rust".to_string()),
+      has_enclosure: false,
+    });
+    assert!(params.skips_empty_feeds());
+  }
+
+  #[test]
+  fn test_feeds_param_defaults() {
+    let params = params("max_entries=10");
+    assert_eq!(params.feed_filter(), FeedFilter::default());
+
+    // Without since the window is the last week
+    let clock = FixedClock(Utc.with_ymd_and_hms(2024, 10, 9, 18, 0, 0).unwrap());
+    let filter = params.entry_filter(Some(Duration::WEEK), &clock);
+    assert_eq!(filter.since, Some(Utc.with_ymd_and_hms(2024, 10, 2, 18, 0, 0).unwrap()));
+    assert_eq!(filter.until, None);
+    assert_eq!(params.entry_filter(None, &clock).since, None);
+    assert!(!params.skips_empty_feeds());
+  }
+
+  #[test]
+  fn test_feeds_param_windows() {
+    let clock = FixedClock(Utc.with_ymd_and_hms(2024, 10, 9, 18, 0, 0).unwrap());
+
+    // The named durations still work, and ISO 8601 ones end at until when it's given
+    let filter = params("duration=month").entry_filter(Some(Duration::WEEK), &clock);
+    assert_eq!(filter.since, Some(Utc.with_ymd_and_hms(2024, 9, 9, 18, 0, 0).unwrap()));
+    let filter = params("duration=PT6H&until=2024-10-01T12:00:00%2B02:00").entry_filter(Some(Duration::WEEK), &clock);
+    assert_eq!(filter.since, Some(Utc.with_ymd_and_hms(2024, 10, 1, 4, 0, 0).unwrap()));
+    assert_eq!(filter.until, Some(Utc.with_ymd_and_hms(2024, 10, 1, 10, 0, 0).unwrap()));
+
+    let uri: Uri = "/feeds?duration=fortnight".parse().unwrap();
+    assert!(Query::<FeedsParam>::try_from_uri(&uri).is_err());
+    let uri: Uri = "/feeds?since=last%20tuesday".parse().unwrap();
+    assert!(Query::<FeedsParam>::try_from_uri(&uri).is_err());
+  }
+}
@@ -0,0 +1,256 @@
+use std::fmt::Display;
+
+use serde::{de::{self, DeserializeOwned}, Deserialize, Deserializer, Serialize};
+use serde_json::{from_value, Value};
+
+use super::FeedDate;
+
+#[derive(Deserialize, Serialize, Debug)]
+pub enum JsonFeedError {
+  Message(String),
+}
+
+impl Display for JsonFeedError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      JsonFeedError::Message(msg) => write!(f, "JSON Feed: {}", msg),
+    }
+  }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct JsonFeedItem {
+  #[serde(deserialize_with = "id")]
+  pub id: String,
+  #[serde(default)]
+  pub url: Option<String>,
+  #[serde(default)]
+  pub external_url: Option<String>,
+  #[serde(default)]
+  pub title: Option<String>,
+  #[serde(default)]
+  pub summary: Option<String>,
+  #[serde(default)]
+  pub content_html: Option<String>,
+  #[serde(default)]
+  pub content_text: Option<String>,
+  #[serde(default)]
+  pub date_published: Option<FeedDate>,
+  #[serde(default)]
+  pub date_modified: Option<FeedDate>,
+  // Version 1.1 lists authors, 1.0 had a single author
+  #[serde(default)]
+  pub authors: Vec<JsonFeedAuthor>,
+  #[serde(default)]
+  pub author: Option<JsonFeedAuthor>,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub image: Option<String>,
+  #[serde(default)]
+  pub attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct JsonFeedAttachment {
+  pub url: String,
+  pub mime_type: String,
+  #[serde(default)]
+  pub size_in_bytes: Option<i64>,
+  #[serde(default)]
+  pub duration_in_seconds: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct JsonFeedAuthor {
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct JsonFeed {
+  pub version: String,
+  #[serde(default)]
+  pub title: Option<String>,
+  #[serde(default)]
+  pub home_page_url: Option<String>,
+  #[serde(default)]
+  pub description: Option<String>,
+  #[serde(default)]
+  pub icon: Option<String>,
+  #[serde(default)]
+  pub favicon: Option<String>,
+  #[serde(deserialize_with = "items")]
+  pub items: Vec<Result<JsonFeedItem, String>>,
+}
+
+fn id<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  // The spec requires a string, but plenty of generators emit numeric ids
+  match Value::deserialize(deserializer)? {
+    Value::String(id) => Ok(id),
+    Value::Number(id) => Ok(id.to_string()),
+    other => Err(de::Error::custom(format!("Invalid JSON Feed item id: {}", other))),
+  }
+}
+
+// Deserializes each item on its own so a malformed one is reported rather than rejecting the feed
+fn items<'de, D, T>(deserializer: D) -> Result<Vec<Result<T, String>>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: DeserializeOwned,
+{
+  let values = Vec::<Value>::deserialize(deserializer)?;
+
+  Ok(values.into_iter()
+    .map(|value| from_value(value).map_err(|e| e.to_string()))
+    .collect())
+}
+
+pub fn is_json_feed(value: &Value) -> bool {
+  value.get("version")
+    .and_then(|version| version.as_str())
+    .is_some_and(|version| version.starts_with("https://jsonfeed.org/version/"))
+}
+
+pub fn json_feed_from_value(value: Value) -> Result<JsonFeed, JsonFeedError> {
+  if !is_json_feed(&value) {
+    return Err(JsonFeedError::Message("Missing JSON Feed version".to_string()));
+  }
+
+  from_value(value).map_err(|e| JsonFeedError::Message(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use super::*;
+
+  fn assert_json_feed_parsed(result: Result<JsonFeed, JsonFeedError>) {
+    match result {
+      Ok(feed) => {
+        assert!(!feed.items.is_empty(), "Expected at least one item");
+
+        let first_item = feed.items[0].as_ref().unwrap();
+        assert_eq!(first_item.id, "https://daringfireball.net/linked/2024/10/09/keyburg");
+        assert_eq!(first_item.url.as_deref(), Some("https://daringfireball.net/linked/2024/10/09/keyburg"));
+        assert_eq!(first_item.title.as_deref(), Some("I Made a Terrible Video Game"));
+        assert_eq!(first_item.date_published.as_ref().and_then(FeedDate::parsed).unwrap().to_string(), "2024-10-09 18:55:25 UTC");
+      }
+      Err(e) => panic!("Parsing failed: {:?}", e),
+    }
+  }
+
+  #[test]
+  fn test_json_feed_version_1_1() {
+    let data = json!({
+      "version": "https://jsonfeed.org/version/1.1",
+      "title": "Daring Fireball",
+      "items": [
+        {
+          "id": "https://daringfireball.net/linked/2024/10/09/keyburg",
+          "url": "https://daringfireball.net/linked/2024/10/09/keyburg",
+          "title": "I Made a Terrible Video Game",
+          "content_html": "<p>Hello</p>",
+          "date_published": "2024-10-09T14:55:25-04:00",
+          "authors": [{ "name": "John Gruber", "url": "https://daringfireball.net" }],
+          "tags": ["games"]
+        }
+      ]
+    });
+
+    let result = json_feed_from_value(data.clone());
+    assert_json_feed_parsed(result);
+
+    let feed = json_feed_from_value(data).unwrap();
+    let item = feed.items[0].as_ref().unwrap();
+    assert_eq!(item.content_html.as_deref(), Some("<p>Hello</p>"));
+    assert_eq!(item.authors[0].name.as_deref(), Some("John Gruber"));
+    assert_eq!(item.tags, vec!["games".to_string()]);
+  }
+
+  #[test]
+  fn test_json_feed_version_1() {
+    let data = json!({
+      "version": "https://jsonfeed.org/version/1",
+      "title": "Daring Fireball",
+      "items": [
+        {
+          "id": "https://daringfireball.net/linked/2024/10/09/keyburg",
+          "url": "https://daringfireball.net/linked/2024/10/09/keyburg",
+          "title": "I Made a Terrible Video Game",
+          "date_published": "2024-10-09T18:55:25Z",
+          "date_modified": "2024-10-10T00:00:00Z"
+        }
+      ]
+    });
+
+    let result = json_feed_from_value(data);
+    assert_json_feed_parsed(result);
+  }
+
+  #[test]
+  fn test_json_feed_numeric_id() {
+    let data = json!({
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [
+        {
+          "id": 568,
+          "url": "https://example.org/568",
+          "content_text": "Untitled microblog post"
+        }
+      ]
+    });
+
+    let feed = json_feed_from_value(data).unwrap();
+    assert_eq!(feed.items[0].as_ref().unwrap().id, "568");
+    assert_eq!(feed.items[0].as_ref().unwrap().title, None);
+    assert_eq!(feed.items[0].as_ref().unwrap().date_published, None);
+  }
+
+  #[test]
+  fn test_json_feed_missing_version() {
+    let data = json!({
+      "items": []
+    });
+
+    let result = json_feed_from_value(data);
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn test_json_feed_invalid_date() {
+    let data = json!({
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [
+        {
+          "id": "1",
+          "url": "https://example.org/1",
+          "date_published": "yesterday"
+        }
+      ]
+    });
+
+    let feed = json_feed_from_value(data).unwrap();
+    assert_eq!(feed.items[0].as_ref().unwrap().date_published, Some(FeedDate::Invalid("yesterday".to_string())));
+  }
+
+  #[test]
+  fn test_json_feed_tolerates_bad_items() {
+    let data = json!({
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [
+        { "url": "https://example.org/no-id" },
+        { "id": "2", "url": "https://example.org/2" }
+      ]
+    });
+
+    let feed = json_feed_from_value(data).unwrap();
+    assert!(feed.items[0].is_err());
+    assert!(feed.items[1].is_ok());
+  }
+}
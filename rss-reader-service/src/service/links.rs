@@ -0,0 +1,127 @@
+use url::Url;
+
+// Resolves the links in one feed. Relative links are taken against the xml:base they were written
+// under, which is itself relative to the URL the feed was fetched from. Links with no xml:base in
+// effect are taken against the channel's own <link>, and then the feed URL.
+pub struct LinkResolver {
+  feed_url: Option<Url>,
+  channel_link: Option<Url>,
+}
+
+impl LinkResolver {
+  pub fn new(feed_url: Option<&str>, channel_link: Option<&str>) -> Self {
+    let feed_url = feed_url.and_then(|url| Url::parse(url.trim()).ok());
+    let channel_link = channel_link
+      .and_then(|link| join(feed_url.as_ref(), link))
+      .filter(|link| matches!(link.scheme(), "http" | "https"));
+
+    Self { feed_url, channel_link }
+  }
+
+  // Base URL for an entry carrying the given xml:base
+  pub fn base(&self, xml_base: Option<&str>) -> Option<Url> {
+    match xml_base {
+      Some(xml_base) => join(self.feed_url.as_ref(), xml_base).or_else(|| self.feed_url.clone()),
+      None => self.channel_link.clone().or_else(|| self.feed_url.clone()),
+    }
+  }
+}
+
+fn join(base: Option<&Url>, link: &str) -> Option<Url> {
+  match base {
+    Some(base) => base.join(link.trim()).ok(),
+    None => Url::parse(link.trim()).ok(),
+  }
+}
+
+// Absolute links are kept exactly as written, since they double as entry identities, and
+// relative ones are left alone when there's nothing to resolve them against
+pub fn resolve(base: Option<&Url>, link: &str) -> String {
+  let link = link.trim();
+  if Url::parse(link).is_ok() {
+    return link.to_string();
+  }
+
+  base.and_then(|base| base.join(link).ok())
+    .map(String::from)
+    .unwrap_or_else(|| link.to_string())
+}
+
+// A resolved link a browser can open or load, for the pages and images a feed points at
+pub fn web_link(base: Option<&Url>, link: &str) -> Option<String> {
+  let link = resolve(base, link);
+  Url::parse(&link).ok()
+    .filter(|url| matches!(url.scheme(), "http" | "https"))
+    .map(|_| link)
+}
+
+// A link resolved against only the xml:base it was written under
+pub fn resolve_xml_base(xml_base: Option<&str>, link: &str) -> String {
+  resolve(xml_base.and_then(|base| Url::parse(base).ok()).as_ref(), link)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FEED_URL: &str = "https://example.org/blog/feed.xml";
+
+  fn resolved(resolver: &LinkResolver, xml_base: Option<&str>, link: &str) -> String {
+    resolve(resolver.base(xml_base).as_ref(), link)
+  }
+
+  #[test]
+  fn test_resolve_against_feed_url() {
+    let resolver = LinkResolver::new(Some(FEED_URL), None);
+    assert_eq!(resolved(&resolver, None, "/posts/foo"), "https://example.org/posts/foo");
+    assert_eq!(resolved(&resolver, None, "posts/foo"), "https://example.org/blog/posts/foo");
+    assert_eq!(resolved(&resolver, None, "//cdn.example.org/foo.mp3"), "https://cdn.example.org/foo.mp3");
+  }
+
+  #[test]
+  fn test_resolve_against_channel_link() {
+    let resolver = LinkResolver::new(Some(FEED_URL), Some("https://www.example.org/journal/"));
+    assert_eq!(resolved(&resolver, None, "posts/foo"), "https://www.example.org/journal/posts/foo");
+
+    // A relative channel link is itself relative to the feed URL
+    let resolver = LinkResolver::new(Some(FEED_URL), Some("/journal/"));
+    assert_eq!(resolved(&resolver, None, "posts/foo"), "https://example.org/journal/posts/foo");
+
+    let resolver = LinkResolver::new(Some(FEED_URL), Some("javascript:void(0)"));
+    assert_eq!(resolved(&resolver, None, "posts/foo"), "https://example.org/blog/posts/foo");
+  }
+
+  #[test]
+  fn test_resolve_against_xml_base() {
+    let resolver = LinkResolver::new(Some(FEED_URL), Some("https://www.example.org/journal/"));
+    assert_eq!(resolved(&resolver, Some("https://static.example.org/2024/"), "foo"), "https://static.example.org/2024/foo");
+
+    // A relative xml:base belongs to the document, not the site its channel links to
+    let resolver = LinkResolver::new(Some(FEED_URL), Some("https://www.example.com/journal/"));
+    assert_eq!(resolved(&resolver, Some("2024/"), "foo"), "https://example.org/blog/2024/foo");
+    assert_eq!(resolved(&resolver, Some("/archive/"), "foo"), "https://example.org/archive/foo");
+    assert_eq!(resolved(&resolver, None, "foo"), "https://www.example.com/journal/foo");
+  }
+
+  #[test]
+  fn test_resolve_keeps_absolute_links() {
+    let resolver = LinkResolver::new(Some(FEED_URL), None);
+    assert_eq!(resolved(&resolver, Some("https://static.example.org/"), " https://Example.org "), "https://Example.org");
+    assert_eq!(resolved(&resolver, None, "mailto:pepper@example.org"), "mailto:pepper@example.org");
+  }
+
+  #[test]
+  fn test_web_link() {
+    let base = Url::parse(FEED_URL).ok();
+    assert_eq!(web_link(base.as_ref(), "/favicon.ico").as_deref(), Some("https://example.org/favicon.ico"));
+    assert_eq!(web_link(base.as_ref(), "javascript:void(0)"), None);
+    assert_eq!(web_link(None, "/favicon.ico"), None);
+  }
+
+  #[test]
+  fn test_resolve_without_base() {
+    let resolver = LinkResolver::new(None, None);
+    assert_eq!(resolved(&resolver, None, "/posts/foo"), "/posts/foo");
+    assert_eq!(resolved(&resolver, Some("https://example.org/"), "/posts/foo"), "https://example.org/posts/foo");
+  }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+
+use url::Url;
+
+use crate::db::Enclosure;
+
+use super::{links::{resolve_xml_base, web_link}, parser::Element, rss::{distinct, number}};
+
+// Podcast and Media RSS details an item or entry can carry alongside its text
+#[derive(Serialize, Debug, Default)]
+pub struct Media {
+  pub enclosures: Vec<Enclosure>,
+  pub duration: Option<i32>,
+  pub episode: Option<i32>,
+  pub season: Option<i32>,
+  pub image_url: Option<String>,
+  pub thumbnails: Vec<String>,
+}
+
+impl Media {
+  // Reads <enclosure>, itunes:* and media:* children; Atom enclosure links are passed in separately
+  pub fn from_element(element: &Element, enclosures: Vec<Enclosure>) -> Self {
+    // Media RSS allows grouping alternative renditions under <media:group>
+    let media_parents: Vec<&Element> = std::iter::once(element)
+      .chain(element.children("media:group"))
+      .collect();
+    let media_contents: Vec<&Element> = media_parents.iter()
+      .flat_map(|parent| parent.children("media:content"))
+      .collect();
+
+    let itunes_duration = element.child_text("itunes:duration").and_then(|duration| duration_secs(&duration));
+
+    let mut all_enclosures = enclosures;
+    // itunes:duration describes the item's own <enclosure>, which podcasts only have one of
+    let mut item_enclosures = element.children("enclosure").filter_map(|enclosure| enclosure_from(enclosure, "type", "length"));
+    if let Some(first) = item_enclosures.next() {
+      all_enclosures.push(Enclosure { duration: first.duration.or(itunes_duration), ..first });
+    }
+    all_enclosures.extend(item_enclosures);
+    for content in media_contents.iter().filter_map(|content| enclosure_from(content, "type", "fileSize")) {
+      match all_enclosures.iter_mut().find(|enclosure| enclosure.url == content.url) {
+        Some(enclosure) => enclosure.duration = enclosure.duration.or(content.duration),
+        None => all_enclosures.push(content),
+      }
+    }
+
+    let thumbnails = media_parents.iter().copied()
+      .chain(media_contents.iter().copied())
+      .flat_map(|parent| parent.children("media:thumbnail"))
+      .filter_map(|thumbnail| url_attribute(thumbnail, "url"));
+
+    Self {
+      enclosures: all_enclosures,
+      duration: itunes_duration
+        .or_else(|| media_contents.iter().find_map(|content| content.attribute("duration")).and_then(duration_secs)),
+      episode: small_number(element.child_text("itunes:episode")),
+      season: small_number(element.child_text("itunes:season")),
+      image_url: element.child("itunes:image").and_then(|image| url_attribute(image, "href")),
+      thumbnails: distinct(thumbnails),
+    }
+  }
+
+  // Links made absolute against the entry's base URL, dropping any a browser shouldn't load
+  pub fn resolve(self, base: Option<&Url>) -> Self {
+    Self {
+      enclosures: self.enclosures.into_iter()
+        .filter_map(|enclosure| Some(Enclosure { url: web_link(base, &enclosure.url)?, ..enclosure }))
+        .collect(),
+      image_url: self.image_url.and_then(|image_url| web_link(base, &image_url)),
+      thumbnails: self.thumbnails.iter().filter_map(|thumbnail| web_link(base, thumbnail)).collect(),
+      ..self
+    }
+  }
+}
+
+// A URL attribute resolved against the element's xml:base, which can be its own
+fn url_attribute(element: &Element, name: &str) -> Option<String> {
+  let url = element.attribute(name)?.trim();
+  (!url.is_empty()).then(|| resolve_xml_base(element.base.as_deref(), url))
+}
+
+pub(super) fn enclosure_from(element: &Element, type_attribute: &str, length_attribute: &str) -> Option<Enclosure> {
+  Some(Enclosure {
+    url: url_attribute(element, "url").or_else(|| url_attribute(element, "href"))?,
+    mime_type: element.attribute(type_attribute).map(|mime_type| mime_type.trim().to_string()),
+    // Publishers often write 0 when they don't know the size
+    length: number(element.attribute(length_attribute).map(|length| length.to_string())).filter(|length| *length > 0),
+    // Only media:content carries one
+    duration: element.attribute("duration").and_then(duration_secs),
+  })
+}
+
+fn small_number(text: Option<String>) -> Option<i32> {
+  number(text).and_then(|number| i32::try_from(number).ok())
+}
+
+// itunes:duration is plain seconds, MM:SS or HH:MM:SS
+fn duration_secs(duration: &str) -> Option<i32> {
+  let duration = duration.trim();
+  if let Ok(seconds) = duration.parse::<f64>() {
+    return (seconds >= 0.0).then_some(seconds as i32);
+  }
+
+  let parts = duration.split(':')
+    .map(|part| part.parse::<u32>().ok())
+    .collect::<Option<Vec<_>>>()?;
+  if !(2..=3).contains(&parts.len()) {
+    return None;
+  }
+  let seconds = parts.iter().fold(0u64, |total, part| total * 60 + *part as u64);
+  i32::try_from(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_duration_secs() {
+    assert_eq!(duration_secs("3600"), Some(3600));
+    assert_eq!(duration_secs(" 1:02:03 "), Some(3723));
+    assert_eq!(duration_secs("45:30"), Some(2730));
+    assert_eq!(duration_secs("1834.5"), Some(1834));
+    assert_eq!(duration_secs("an hour"), None);
+    assert_eq!(duration_secs("1:2:3:4"), None);
+  }
+}
@@ -1,12 +1,38 @@
 mod feeds;
+mod categories;
 mod xml;
 mod rss;
 mod atom;
+mod jsonfeed;
+mod rdf;
 mod cache;
+mod date;
+mod parser;
+mod media;
+mod sanitize;
+mod links;
+mod charset;
+mod discover;
+mod opml;
+mod timeline;
+mod planet;
+mod window;
 
 pub use feeds::*;
+pub use categories::*;
+pub use timeline::*;
+pub use planet::*;
 pub use cache::*;
 
 use xml::*;
+pub use xml::parse_feed_xml;
 use rss::*;
-use atom::*;
\ No newline at end of file
+use atom::*;
+use jsonfeed::*;
+use rdf::*;
+use date::*;
+use sanitize::*;
+use links::*;
+use opml::*;
+pub use window::*;
+pub use discover::*;
\ No newline at end of file
@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::Utc;
+use quick_xml::escape::escape;
+use serde::Serialize;
+
+use super::parser::{read_document, Element, XmlError};
+
+// Where feeds go when the document doesn't group them
+pub const DEFAULT_CATEGORY: &str = "Uncategorized";
+
+#[derive(Serialize, Debug)]
+pub enum OpmlError {
+  Message(String),
+}
+
+impl Display for OpmlError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      OpmlError::Message(msg) => write!(f, "OPML: {}", msg),
+    }
+  }
+}
+
+impl From<XmlError> for OpmlError {
+  fn from(e: XmlError) -> Self {
+    OpmlError::Message(e.to_string())
+  }
+}
+
+// A subscription outline, with the group it was nested in as its category
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct OpmlOutline {
+  pub name: String,
+  // None for outlines that aren't feeds, which are reported rather than dropped
+  pub url: Option<String>,
+  pub category: String,
+}
+
+// Attribute names are camelCase in the spec, but plenty of exporters lowercase them
+fn attribute<'a>(outline: &'a Element, name: &str) -> Option<&'a str> {
+  outline.attributes.iter()
+    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+    .map(|(_, value)| value.trim())
+    .filter(|value| !value.is_empty())
+}
+
+fn outline_name(outline: &Element) -> Option<&str> {
+  attribute(outline, "title").or(attribute(outline, "text"))
+}
+
+// OPML 2.0's category attribute holds comma separated slash paths, like "/Tech/Rust"
+fn category_attribute(outline: &Element) -> Option<String> {
+  attribute(outline, "category")?
+    .split(',')
+    .filter_map(|path| path.trim().trim_matches('/').rsplit('/').next())
+    .map(str::trim)
+    .find(|category| !category.is_empty())
+    .map(String::from)
+}
+
+fn collect_outlines(parent: &Element, group: Option<&str>, outlines: &mut Vec<OpmlOutline>) {
+  for outline in parent.children("outline") {
+    let url = attribute(outline, "xmlUrl");
+    let is_group = url.is_none() && outline.children("outline").next().is_some();
+
+    if is_group {
+      // Nested groups flatten to the innermost one, since a feed has a single category
+      collect_outlines(outline, outline_name(outline).or(group), outlines);
+      continue;
+    }
+
+    outlines.push(OpmlOutline {
+      name: outline_name(outline).or(url).unwrap_or_default().to_string(),
+      url: url.map(String::from),
+      category: group.map(String::from)
+        .or_else(|| category_attribute(outline))
+        .unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+    });
+  }
+}
+
+// Every leaf outline in an OPML 1.0 or 2.0 document, in document order
+pub fn read_opml(xml: &str) -> Result<Vec<OpmlOutline>, OpmlError> {
+  // Outlines nest, so the whole document is read as one tree rather than entry by entry
+  let document = read_document(xml, None, "", |_| ())?;
+
+  if document.root.name != "opml" {
+    return Err(OpmlError::Message(format!("Expected <opml> but found <{}>", document.root.name)));
+  }
+  let body = document.root.child("body")
+    .ok_or_else(|| OpmlError::Message("Missing <body>".to_string()))?;
+
+  let mut outlines = Vec::new();
+  collect_outlines(body, None, &mut outlines);
+  Ok(outlines)
+}
+
+// An OPML 2.0 document with a group outline for each category, both sorted by name
+pub fn write_opml(outlines: &[OpmlOutline]) -> String {
+  let mut categories: BTreeMap<&str, Vec<&OpmlOutline>> = BTreeMap::new();
+  for outline in outlines.iter().filter(|outline| outline.url.is_some()) {
+    categories.entry(outline.category.as_str()).or_default().push(outline);
+  }
+
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+  xml.push_str("  <head>\n    <title>RSS Reader Feeds</title>\n");
+  xml.push_str(&format!("    <dateCreated>{}</dateCreated>\n  </head>\n  <body>\n", Utc::now().to_rfc2822()));
+
+  for (category, mut feeds) in categories {
+    feeds.sort_by(|a, b| a.name.cmp(&b.name));
+    let category = escape(category);
+    xml.push_str(&format!("    <outline text=\"{category}\" title=\"{category}\">\n"));
+    for feed in feeds {
+      let name = escape(feed.name.as_str());
+      let url = escape(feed.url.as_deref().unwrap_or_default());
+      xml.push_str(&format!("      <outline type=\"rss\" text=\"{name}\" title=\"{name}\" xmlUrl=\"{url}\"/>\n"));
+    }
+    xml.push_str("    </outline>\n");
+  }
+
+  xml.push_str("  </body>\n</opml>\n");
+  xml
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn outline(name: &str, url: &str, category: &str) -> OpmlOutline {
+    OpmlOutline { name: name.to_string(), url: Some(url.to_string()), category: category.to_string() }
+  }
+
+  #[test]
+  fn test_read_opml_groups() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+      <opml version="1.0">
+        <head><title>Subscriptions</title></head>
+        <body>
+          <outline text="Tech">
+            <outline text="This Week in Rust" type="rss" xmlUrl="https://this-week-in-rust.org/rss.xml" htmlUrl="https://this-week-in-rust.org/"/>
+            <outline title="Languages" text="Langs">
+              <outline text="Go Blog" xmlurl="https://go.dev/blog/feed.atom"/>
+            </outline>
+          </outline>
+          <outline text="Hacker News" xmlUrl="https://news.ycombinator.com/rss" category="/News/Aggregators,/Tech"/>
+          <outline text="Loose" xmlUrl="https://example.org/feed.xml"/>
+          <outline text="Just a note"/>
+        </body>
+      </opml>"#;
+
+    assert_eq!(read_opml(xml).unwrap(), vec![
+      outline("This Week in Rust", "https://this-week-in-rust.org/rss.xml", "Tech"),
+      outline("Go Blog", "https://go.dev/blog/feed.atom", "Languages"),
+      outline("Hacker News", "https://news.ycombinator.com/rss", "Aggregators"),
+      outline("Loose", "https://example.org/feed.xml", DEFAULT_CATEGORY),
+      OpmlOutline { name: "Just a note".to_string(), url: None, category: DEFAULT_CATEGORY.to_string() },
+    ]);
+  }
+
+  #[test]
+  fn test_read_opml_not_opml() {
+    assert!(read_opml("<rss><channel></channel></rss>").is_err());
+    assert!(read_opml("<opml version=\"2.0\"><head></head></opml>").is_err());
+  }
+
+  #[test]
+  fn test_opml_round_trip() {
+    let outlines = vec![
+      outline("Global Hunger Index", "https://www.globalhungerindex.org/atom.xml", "News"),
+      outline("Fish & \"Chips\" <Weekly>", "https://example.org/feed?a=1&b=2", "Food & Drink"),
+      outline("Alpha", "https://example.org/alpha.xml", "News"),
+    ];
+
+    let xml = write_opml(&outlines);
+    let mut read = read_opml(&xml).unwrap();
+    read.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut expected = outlines.clone();
+    expected.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(read, expected);
+
+    // Writing what was read gives back the same document, apart from its date
+    let without_date = |xml: &str| xml.lines().filter(|line| !line.contains("dateCreated")).collect::<Vec<_>>().join("\n");
+    assert_eq!(without_date(&write_opml(&read)), without_date(&xml));
+  }
+
+  #[test]
+  fn test_write_opml_grouped_by_category() {
+    let xml = write_opml(&[
+      outline("B", "https://example.org/b.xml", "Tech"),
+      outline("A", "https://example.org/a.xml", "Tech"),
+      outline("C", "https://example.org/c.xml", "News"),
+    ]);
+
+    let news = xml.find("text=\"News\"").unwrap();
+    let tech = xml.find("text=\"Tech\"").unwrap();
+    let a = xml.find("text=\"A\"").unwrap();
+    let b = xml.find("text=\"B\"").unwrap();
+    assert!(news < tech && tech < a && a < b);
+  }
+}
@@ -0,0 +1,448 @@
+use std::fmt::Display;
+
+use quick_xml::{escape::escape, events::{BytesStart, Event}, name::{Namespace, ResolveResult}, NsReader};
+use url::Url;
+
+#[derive(Debug)]
+pub enum XmlError {
+  Message(String),
+}
+
+impl Display for XmlError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      XmlError::Message(msg) => write!(f, "XML: {}", msg),
+    }
+  }
+}
+
+// Prefixes elements are known by regardless of how a document declares them, so
+// <dc:date> and <purl:date> read the same. An empty prefix is the feed's own vocabulary.
+const NAMESPACES: &[(&str, &str)] = &[
+  ("http://purl.org/rss/1.0/", ""),
+  ("http://my.netscape.com/rdf/simple/0.9/", ""),
+  ("http://backend.userland.com/rss2", ""),
+  ("http://www.w3.org/2005/Atom", "atom"),
+  ("http://purl.org/atom/ns#", "atom"),
+  ("http://www.w3.org/1999/02/22-rdf-syntax-ns#", "rdf"),
+  ("http://www.w3.org/XML/1998/namespace", "xml"),
+  ("http://purl.org/dc/elements/1.1/", "dc"),
+  ("http://purl.org/dc/terms/", "dcterms"),
+  ("http://purl.org/rss/1.0/modules/syndication/", "sy"),
+  ("http://purl.org/rss/1.0/modules/content/", "content"),
+  ("http://purl.org/rss/1.0/modules/slash/", "slash"),
+  ("http://wellformedweb.org/CommentAPI/", "wfw"),
+  ("http://www.itunes.com/dtds/podcast-1.0.dtd", "itunes"),
+  ("http://search.yahoo.com/mrss/", "media"),
+];
+
+// HTML entities that feeds routinely use without declaring them
+const HTML_ENTITIES: &[(&str, &str)] = &[
+  ("nbsp", "\u{a0}"), ("ndash", "\u{2013}"), ("mdash", "\u{2014}"), ("hellip", "\u{2026}"),
+  ("lsquo", "\u{2018}"), ("rsquo", "\u{2019}"), ("ldquo", "\u{201c}"), ("rdquo", "\u{201d}"),
+  ("laquo", "\u{ab}"), ("raquo", "\u{bb}"), ("bull", "\u{2022}"), ("middot", "\u{b7}"),
+  ("copy", "\u{a9}"), ("reg", "\u{ae}"), ("trade", "\u{2122}"), ("deg", "\u{b0}"),
+  ("euro", "\u{20ac}"), ("pound", "\u{a3}"), ("times", "\u{d7}"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+  Element(Element),
+  Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Element {
+  pub name: String,
+  pub attributes: Vec<(String, String)>,
+  pub children: Vec<Node>,
+  // xml:base in effect for the element, its own or inherited, resolved against the URL the document
+  // came from. None when no element above declares one, and left relative when there's no URL.
+  pub base: Option<String>,
+}
+
+impl Element {
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    self.attributes.iter()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  pub fn elements(&self) -> impl Iterator<Item = &Element> {
+    self.children.iter().filter_map(|node| match node {
+      Node::Element(element) => Some(element),
+      Node::Text(_) => None,
+    })
+  }
+
+  pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+    self.elements().filter(move |element| element.name == name)
+  }
+
+  pub fn child(&self, name: &str) -> Option<&Element> {
+    self.elements().find(|element| element.name == name)
+  }
+
+  // Trimmed text of the element and everything inside it, None when there isn't any
+  pub fn text(&self) -> Option<String> {
+    let mut text = String::new();
+    self.collect_text(&mut text);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+  }
+
+  pub fn child_text(&self, name: &str) -> Option<String> {
+    self.child(name).and_then(Element::text)
+  }
+
+  // Markup of everything inside the element, for content carried inline as XHTML
+  pub fn inner_xml(&self) -> String {
+    let mut xml = String::new();
+    for node in &self.children {
+      match node {
+        Node::Text(text) => xml.push_str(&escape(text.as_str())),
+        Node::Element(element) => element.write_xml(&mut xml),
+      }
+    }
+    xml
+  }
+
+  fn write_xml(&self, xml: &mut String) {
+    xml.push('<');
+    xml.push_str(&self.name);
+    for (key, value) in &self.attributes {
+      xml.push_str(&format!(" {}=\"{}\"", key, escape(value.as_str())));
+    }
+    if self.children.is_empty() {
+      xml.push_str(" />");
+      return;
+    }
+    xml.push('>');
+    xml.push_str(&self.inner_xml());
+    xml.push_str(&format!("</{}>", self.name));
+  }
+
+  fn collect_text(&self, text: &mut String) {
+    for node in &self.children {
+      match node {
+        Node::Text(t) => text.push_str(t),
+        Node::Element(element) => element.collect_text(text),
+      }
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Document {
+  // Root element holding everything except the entries, which were handed out as they closed
+  pub root: Element,
+  // Set when the markup broke partway through and reading stopped early
+  pub warnings: Vec<String>,
+}
+
+fn prefix_for(namespace: &[u8], feed_namespace: Option<&[u8]>) -> Option<&'static str> {
+  if Some(namespace) == feed_namespace {
+    return Some("");
+  }
+  NAMESPACES.iter()
+    .find(|(uri, _)| uri.as_bytes() == namespace)
+    .map(|(_, prefix)| *prefix)
+}
+
+// The root's namespace is the feed's own vocabulary, so an Atom document reads as unprefixed
+// Atom. RDF is the exception, its vocabulary lives in the channel and items.
+fn feed_namespace_of<'a>(resolved: &ResolveResult<'a>) -> Option<&'a [u8]> {
+  match resolved {
+    ResolveResult::Bound(Namespace(namespace)) if prefix_for(namespace, None) != Some("rdf") => Some(namespace),
+    _ => None,
+  }
+}
+
+fn qualified_name(resolved: ResolveResult, local: &[u8], written: &[u8], feed_namespace: Option<&[u8]>) -> String {
+  let name = match resolved {
+    ResolveResult::Bound(Namespace(namespace)) => match prefix_for(namespace, feed_namespace) {
+      Some("") => local.to_vec(),
+      Some(prefix) => [prefix.as_bytes(), b":", local].concat(),
+      None => written.to_vec(),
+    },
+    ResolveResult::Unbound => local.to_vec(),
+    ResolveResult::Unknown(_) => written.to_vec(),
+  };
+  String::from_utf8_lossy(&name).into_owned()
+}
+
+fn html_entity(entity: &str) -> Option<&'static str> {
+  HTML_ENTITIES.iter()
+    .find(|(name, _)| *name == entity)
+    .map(|(_, value)| *value)
+}
+
+fn element(reader: &NsReader<&[u8]>, start: &BytesStart, feed_namespace: Option<&[u8]>, parent: Option<&Element>, document_url: Option<&str>) -> Element {
+  let (resolved, local) = reader.resolve_element(start.name());
+  let name = qualified_name(resolved, local.into_inner(), start.name().into_inner(), feed_namespace);
+
+  let attributes = start.attributes()
+    .filter_map(|attribute| attribute.ok())
+    .filter(|attribute| attribute.key.as_namespace_binding().is_none())
+    .map(|attribute| {
+      let (resolved, local) = reader.resolve_attribute(attribute.key);
+      let key = qualified_name(resolved, local.into_inner(), attribute.key.into_inner(), feed_namespace);
+      let value = attribute.unescape_value_with(html_entity)
+        .map(|value| value.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(&attribute.value).into_owned());
+      (key, value)
+    })
+    .collect();
+
+  let mut element = Element { name, attributes, children: Vec::new(), base: None };
+  let inherited = parent.and_then(|parent| parent.base.as_deref());
+  element.base = xml_base(inherited, element.attribute("xml:base"), document_url);
+  element
+}
+
+// A relative xml:base is taken against the one above it, and the outermost against the document's URL
+fn xml_base(inherited: Option<&str>, own: Option<&str>, document_url: Option<&str>) -> Option<String> {
+  let Some(own) = own.map(str::trim) else {
+    return inherited.map(|base| base.to_string());
+  };
+
+  inherited.or(document_url).and_then(|inherited| Url::parse(inherited).ok())
+    .and_then(|inherited| inherited.join(own).ok())
+    .map(String::from)
+    .or_else(|| Some(own.to_string()))
+}
+
+// Streams a feed document, building one entry at a time and handing it to on_entry as soon as it
+// closes, so memory follows the largest entry rather than the whole document. Entries are the
+// entry_name elements directly under the root or one level below it (<rss><channel><item>).
+// document_url is where the document was fetched from, for resolving xml:base.
+pub fn read_document<F>(xml: &str, document_url: Option<&str>, entry_name: &str, mut on_entry: F) -> Result<Document, XmlError>
+where
+  F: FnMut(Element),
+{
+  let mut reader = NsReader::from_str(xml);
+  let mut stack: Vec<Element> = Vec::new();
+  let mut root: Option<Element> = None;
+  let mut feed_namespace: Option<Vec<u8>> = None;
+  let mut warnings = Vec::new();
+
+  let mut close = |element: Element, stack: &mut Vec<Element>, root: &mut Option<Element>| {
+    let depth = stack.len();
+    match stack.last_mut() {
+      Some(_) if element.name == entry_name && depth <= 2 => on_entry(element),
+      Some(parent) => parent.children.push(Node::Element(element)),
+      None => *root = Some(element),
+    }
+  };
+
+  loop {
+    match reader.read_event() {
+      Ok(Event::Start(start)) => {
+        if stack.is_empty() && root.is_none() {
+          let (resolved, _) = reader.resolve_element(start.name());
+          feed_namespace = feed_namespace_of(&resolved).map(|namespace| namespace.to_vec());
+        }
+        let element = element(&reader, &start, feed_namespace.as_deref(), stack.last(), document_url);
+        stack.push(element);
+      },
+      Ok(Event::Empty(start)) => {
+        let element = element(&reader, &start, feed_namespace.as_deref(), stack.last(), document_url);
+        close(element, &mut stack, &mut root);
+      },
+      Ok(Event::End(_)) => {
+        if let Some(element) = stack.pop() {
+          close(element, &mut stack, &mut root);
+        }
+      },
+      Ok(Event::Text(text)) => {
+        if let Some(parent) = stack.last_mut() {
+          let text = text.unescape_with(html_entity)
+            .map(|text| text.into_owned())
+            .unwrap_or_else(|_| String::from_utf8_lossy(&text).into_owned());
+          parent.children.push(Node::Text(text));
+        }
+      },
+      Ok(Event::CData(cdata)) => {
+        if let Some(parent) = stack.last_mut() {
+          parent.children.push(Node::Text(String::from_utf8_lossy(&cdata).into_owned()));
+        }
+      },
+      Ok(Event::Eof) => break,
+      Ok(_) => (),
+      Err(e) => {
+        if stack.is_empty() && root.is_none() {
+          return Err(XmlError::Message(e.to_string()));
+        }
+        // Keep whatever was read before the markup broke
+        warnings.push(format!("Stopped reading at byte {}: {}", reader.buffer_position(), e));
+        break;
+      }
+    }
+  }
+
+  while let Some(element) = stack.pop() {
+    close(element, &mut stack, &mut root);
+  }
+
+  match root {
+    Some(root) => Ok(Document { root, warnings }),
+    None => Err(XmlError::Message("Document has no root element".to_string())),
+  }
+}
+
+// Name of the root element, read without going any further into the document
+pub fn root_name(xml: &str) -> Result<String, XmlError> {
+  let mut reader = NsReader::from_str(xml);
+  loop {
+    match reader.read_resolved_event() {
+      Ok((resolved, Event::Start(start) | Event::Empty(start))) => {
+        let feed_namespace = feed_namespace_of(&resolved);
+        return Ok(qualified_name(resolved, start.local_name().into_inner(), start.name().into_inner(), feed_namespace));
+      },
+      Ok((_, Event::Eof)) => return Err(XmlError::Message("Document has no root element".to_string())),
+      Ok(_) => (),
+      Err(e) => return Err(XmlError::Message(e.to_string())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_entries(xml: &str, entry_name: &str) -> (Document, Vec<Element>) {
+    read_entries_at(xml, None, entry_name)
+  }
+
+  fn read_entries_at(xml: &str, document_url: Option<&str>, entry_name: &str) -> (Document, Vec<Element>) {
+    let mut entries = Vec::new();
+    let document = read_document(xml, document_url, entry_name, |entry| entries.push(entry)).unwrap();
+    (document, entries)
+  }
+
+  #[test]
+  fn test_read_document_streams_entries() {
+    let xml = r#"<?xml version="1.0"?>
+      <rss version="2.0">
+        <channel>
+          <title>Example</title>
+          <item><title>One</title></item>
+          <item><title>Two</title></item>
+        </channel>
+      </rss>"#;
+
+    let (document, entries) = read_entries(xml, "item");
+    assert_eq!(document.root.name, "rss");
+    let channel = document.root.child("channel").unwrap();
+    assert_eq!(channel.child_text("title").as_deref(), Some("Example"));
+    assert_eq!(channel.children("item").count(), 0);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].child_text("title").as_deref(), Some("Two"));
+    assert!(document.warnings.is_empty());
+  }
+
+  #[test]
+  fn test_read_document_single_entry() {
+    let xml = "<rss><channel><item><title>Only</title></item></channel></rss>";
+    let (_, entries) = read_entries(xml, "item");
+    assert_eq!(entries.len(), 1);
+  }
+
+  #[test]
+  fn test_read_document_namespaces() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:purl="http://purl.org/dc/elements/1.1/" xmlns:x="urn:unknown">
+        <entry>
+          <purl:creator>Pepper</purl:creator>
+          <x:custom>kept</x:custom>
+          <link href="https://example.org/1" xml:base="https://example.org/" />
+        </entry>
+      </feed>"#;
+
+    let (document, entries) = read_entries(xml, "entry");
+    assert_eq!(document.root.name, "feed");
+    assert_eq!(entries[0].child_text("dc:creator").as_deref(), Some("Pepper"));
+    assert_eq!(entries[0].child_text("x:custom").as_deref(), Some("kept"));
+    let link = entries[0].child("link").unwrap();
+    assert_eq!(link.attribute("href"), Some("https://example.org/1"));
+    assert_eq!(link.attribute("xml:base"), Some("https://example.org/"));
+  }
+
+  #[test]
+  fn test_read_document_text() {
+    let xml = r#"<rss><channel><item>
+        <title>Fish &amp; Chips&nbsp;&mdash; <![CDATA[<b>cheap</b>]]></title>
+        <description><p>Hello <b>world</b>!</p></description>
+      </item></channel></rss>"#;
+
+    let (_, entries) = read_entries(xml, "item");
+    assert_eq!(entries[0].child_text("title").as_deref(), Some("Fish & Chips\u{a0}\u{2014} <b>cheap</b>"));
+    assert_eq!(entries[0].child_text("description").as_deref(), Some("Hello world!"));
+  }
+
+  #[test]
+  fn test_read_document_xml_base() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:base="https://example.org/blog/">
+        <entry><id>1</id></entry>
+        <entry xml:base="2024/"><link xml:base="https://static.example.org/" href="a" /></entry>
+      </feed>"#;
+
+    let (document, entries) = read_entries(xml, "entry");
+    assert_eq!(document.root.base.as_deref(), Some("https://example.org/blog/"));
+    assert_eq!(entries[0].base.as_deref(), Some("https://example.org/blog/"));
+    assert_eq!(entries[1].base.as_deref(), Some("https://example.org/blog/2024/"));
+    assert_eq!(entries[1].child("link").unwrap().base.as_deref(), Some("https://static.example.org/"));
+
+    let (_, entries) = read_entries(r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry xml:base="/posts/" /></feed>"#, "entry");
+    assert_eq!(entries[0].base.as_deref(), Some("/posts/"));
+  }
+
+  #[test]
+  fn test_read_document_relative_xml_base() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:base="/archive/">
+        <link href="https://www.example.com/" />
+        <entry xml:base="2024/"><link href="a" /></entry>
+      </feed>"#;
+
+    let (document, entries) = read_entries_at(xml, Some("https://example.org/blog/feed.xml"), "entry");
+    assert_eq!(document.root.base.as_deref(), Some("https://example.org/archive/"));
+    assert_eq!(entries[0].base.as_deref(), Some("https://example.org/archive/2024/"));
+    assert_eq!(entries[0].child("link").unwrap().base.as_deref(), Some("https://example.org/archive/2024/"));
+
+    // Nothing declares an xml:base, so there's none in effect
+    let (document, _) = read_entries_at("<rss><channel><item /></channel></rss>", Some("https://example.org/blog/feed.xml"), "item");
+    assert_eq!(document.root.base, None);
+  }
+
+  #[test]
+  fn test_inner_xml() {
+    let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry><content type="xhtml">
+        <div xmlns="http://www.w3.org/1999/xhtml"><p class="intro">Fish &amp; <b>chips</b></p><br/></div>
+      </content></entry></feed>"#;
+
+    let (_, entries) = read_entries(xml, "entry");
+    let div = entries[0].child("content").unwrap().child("div").unwrap();
+    assert_eq!(div.inner_xml(), "<p class=\"intro\">Fish &amp; <b>chips</b></p><br />");
+  }
+
+  #[test]
+  fn test_read_document_broken_markup() {
+    let xml = "<rss><channel><item><title>One</title></item><item><title>Two</title></oops></item></channel></rss>";
+    let (document, entries) = read_entries(xml, "item");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].child_text("title").as_deref(), Some("One"));
+    assert_eq!(document.warnings.len(), 1);
+  }
+
+  #[test]
+  fn test_read_document_not_xml() {
+    assert!(read_document("", None, "item", |_| ()).is_err());
+    assert!(read_document("just some text", None, "item", |_| ()).is_err());
+  }
+
+  #[test]
+  fn test_root_name() {
+    assert_eq!(root_name("<?xml version=\"1.0\"?><rss version=\"2.0\"/>").unwrap(), "rss");
+    assert_eq!(root_name("<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>").unwrap(), "feed");
+    assert_eq!(root_name("<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"></rdf:RDF>").unwrap(), "rdf:RDF");
+  }
+}
@@ -0,0 +1,508 @@
+use axum::{extract::{Path, Query, State}, http::{header, HeaderMap, Uri}, response::{IntoResponse, Response}};
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::escape::escape;
+use serde_json::{json, Value};
+use shuttle_runtime::SecretStore;
+
+use crate::{db::{CategoryDataSource, EntryDataSource, TimelineEntry}, AppState};
+
+use super::{FeedsParam, SystemClock};
+
+const DEFAULT_ENTRIES: usize = 50;
+const MAX_ENTRIES: usize = 200;
+const GENERATOR: &str = "rss-reader-service";
+
+// The collection of entries from every feed, or one category's, as a document of its own
+pub struct Planet<'a> {
+  pub title: String,
+  // Where the document itself is served, which also serves as its id
+  pub self_url: String,
+  // The page the collection belongs to
+  pub site_url: String,
+  pub entries: &'a [TimelineEntry],
+}
+
+impl Planet<'_> {
+  // Latest change to any entry, so an unchanged collection keeps its date
+  fn updated(&self) -> DateTime<Utc> {
+    self.entries.iter()
+      .map(|entry| entry.entry.updated_date.unwrap_or(entry.entry.created_date))
+      .max()
+      .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+  }
+}
+
+// Entries from different feeds can share an identity, so the collection names them by its own id
+fn entry_id(entry: &TimelineEntry) -> String {
+  format!("urn:rss-reader:entry:{}", entry.entry.id)
+}
+
+fn atom_date(date: DateTime<Utc>) -> String {
+  date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn body(entry: &TimelineEntry) -> Option<&str> {
+  entry.entry.content_html.as_deref().or(entry.entry.summary.as_deref())
+}
+
+pub fn write_rss(planet: &Planet) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n");
+  xml.push_str(&format!("  <title>{}</title>\n", escape(planet.title.as_str())));
+  xml.push_str(&format!("  <link>{}</link>\n", escape(planet.site_url.as_str())));
+  xml.push_str(&format!("  <description>Entries collected from {}</description>\n", escape(planet.title.as_str())));
+  xml.push_str(&format!("  <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{}\"/>\n", escape(planet.self_url.as_str())));
+  xml.push_str(&format!("  <lastBuildDate>{}</lastBuildDate>\n", planet.updated().to_rfc2822()));
+  xml.push_str(&format!("  <generator>{GENERATOR}</generator>\n"));
+
+  for timeline_entry in planet.entries {
+    let entry = &timeline_entry.entry;
+    xml.push_str("  <item>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape(entry.title.as_str())));
+    xml.push_str(&format!("    <link>{}</link>\n", escape(entry.url.as_str())));
+    xml.push_str(&format!("    <guid isPermaLink=\"false\">{}</guid>\n", entry_id(timeline_entry)));
+    xml.push_str(&format!("    <pubDate>{}</pubDate>\n", entry.created_date.to_rfc2822()));
+    if let Some(body) = body(timeline_entry) {
+      xml.push_str(&format!("    <description>{}</description>\n", escape(body)));
+    }
+    for author in &entry.authors {
+      xml.push_str(&format!("    <dc:creator>{}</dc:creator>\n", escape(author.as_str())));
+    }
+    for category in &entry.categories {
+      xml.push_str(&format!("    <category>{}</category>\n", escape(category.as_str())));
+    }
+    if let Some(comments_url) = &entry.comments_url {
+      xml.push_str(&format!("    <comments>{}</comments>\n", escape(comments_url.as_str())));
+    }
+    // RSS allows a single enclosure, and requires its length even when it's unknown
+    if let Some(enclosure) = entry.enclosures.first() {
+      xml.push_str(&format!(
+        "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+        escape(enclosure.url.as_str()),
+        enclosure.length.unwrap_or(0),
+        escape(enclosure.mime_type.as_deref().unwrap_or("application/octet-stream")),
+      ));
+    }
+    xml.push_str(&format!(
+      "    <source url=\"{}\">{}</source>\n",
+      escape(timeline_entry.feed_url.as_str()),
+      escape(timeline_entry.feed_name.as_str()),
+    ));
+    xml.push_str("  </item>\n");
+  }
+
+  xml.push_str("</channel>\n</rss>\n");
+  xml
+}
+
+pub fn write_atom(planet: &Planet) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+  xml.push_str(&format!("  <id>{}</id>\n", escape(planet.self_url.as_str())));
+  xml.push_str(&format!("  <title>{}</title>\n", escape(planet.title.as_str())));
+  xml.push_str(&format!("  <updated>{}</updated>\n", atom_date(planet.updated())));
+  xml.push_str(&format!("  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n", escape(planet.self_url.as_str())));
+  xml.push_str(&format!("  <link rel=\"alternate\" href=\"{}\"/>\n", escape(planet.site_url.as_str())));
+  // Entries without authors of their own fall back on the feed's, which Atom requires one of
+  xml.push_str(&format!("  <author><name>{GENERATOR}</name></author>\n"));
+  xml.push_str(&format!("  <generator>{GENERATOR}</generator>\n"));
+
+  for timeline_entry in planet.entries {
+    let entry = &timeline_entry.entry;
+    xml.push_str("  <entry>\n");
+    xml.push_str(&format!("    <id>{}</id>\n", entry_id(timeline_entry)));
+    xml.push_str(&format!("    <title type=\"text\">{}</title>\n", escape(entry.title.as_str())));
+    xml.push_str(&format!("    <link rel=\"alternate\" href=\"{}\"/>\n", escape(entry.url.as_str())));
+    xml.push_str(&format!("    <published>{}</published>\n", atom_date(entry.created_date)));
+    xml.push_str(&format!("    <updated>{}</updated>\n", atom_date(entry.updated_date.unwrap_or(entry.created_date))));
+    for author in &entry.authors {
+      xml.push_str(&format!("    <author><name>{}</name></author>\n", escape(author.as_str())));
+    }
+    for category in &entry.categories {
+      xml.push_str(&format!("    <category term=\"{}\"/>\n", escape(category.as_str())));
+    }
+    if let Some(summary) = &entry.summary {
+      xml.push_str(&format!("    <summary type=\"html\">{}</summary>\n", escape(summary.as_str())));
+    }
+    if let Some(content) = &entry.content_html {
+      xml.push_str(&format!("    <content type=\"html\">{}</content>\n", escape(content.as_str())));
+    }
+    for enclosure in entry.enclosures.iter() {
+      let mime_type = enclosure.mime_type.as_deref()
+        .map(|mime_type| format!(" type=\"{}\"", escape(mime_type)))
+        .unwrap_or_default();
+      let length = enclosure.length
+        .map(|length| format!(" length=\"{length}\""))
+        .unwrap_or_default();
+      xml.push_str(&format!("    <link rel=\"enclosure\" href=\"{}\"{mime_type}{length}/>\n", escape(enclosure.url.as_str())));
+    }
+    xml.push_str("    <source>\n");
+    xml.push_str(&format!("      <id>{}</id>\n", escape(timeline_entry.feed_url.as_str())));
+    xml.push_str(&format!("      <title>{}</title>\n", escape(timeline_entry.feed_name.as_str())));
+    xml.push_str(&format!("      <link rel=\"self\" href=\"{}\"/>\n", escape(timeline_entry.feed_url.as_str())));
+    xml.push_str("    </source>\n");
+    xml.push_str("  </entry>\n");
+  }
+
+  xml.push_str("</feed>\n");
+  xml
+}
+
+pub fn write_json_feed(planet: &Planet) -> Value {
+  let items: Vec<Value> = planet.entries.iter().map(|timeline_entry| {
+    let entry = &timeline_entry.entry;
+    json!({
+      "id": entry_id(timeline_entry),
+      "url": entry.url,
+      "title": entry.title,
+      "content_html": body(timeline_entry),
+      // JSON Feed summaries are plain text
+      "summary": entry.preview,
+      "image": entry.image_url,
+      "date_published": entry.created_date.to_rfc3339(),
+      "date_modified": entry.updated_date.map(|date| date.to_rfc3339()),
+      "authors": entry.authors.iter().map(|author| json!({ "name": author })).collect::<Vec<_>>(),
+      "tags": entry.categories,
+      "attachments": entry.enclosures.iter().map(|enclosure| json!({
+        "url": enclosure.url,
+        "mime_type": enclosure.mime_type.as_deref().unwrap_or("application/octet-stream"),
+        "size_in_bytes": enclosure.length,
+        "duration_in_seconds": enclosure.duration,
+      })).collect::<Vec<_>>(),
+      // Extensions are underscore prefixed
+      "_source": {
+        "title": timeline_entry.feed_name,
+        "feed_url": timeline_entry.feed_url,
+        "category": timeline_entry.category,
+      },
+    })
+  }).collect();
+
+  json!({
+    "version": "https://jsonfeed.org/version/1.1",
+    "title": planet.title,
+    "home_page_url": planet.site_url,
+    "feed_url": planet.self_url,
+    "items": items,
+  })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanetFormat {
+  Rss,
+  Atom,
+  JsonFeed,
+}
+
+// Origin the service is reached at, which feeds need to point back at themselves. PUBLIC_BASE_URL
+// keeps a document's identity stable whoever asks; the request's own Host and X-Forwarded-Proto
+// are only trusted when it isn't set.
+fn public_base(configured: Option<String>, headers: &HeaderMap) -> String {
+  if let Some(base) = configured {
+    return base.trim().trim_end_matches('/').to_string();
+  }
+
+  let host = headers.get(header::HOST)
+    .and_then(|host| host.to_str().ok())
+    .unwrap_or("localhost");
+  let scheme = headers.get("x-forwarded-proto")
+    .and_then(|proto| proto.to_str().ok())
+    .unwrap_or(if host.starts_with("localhost") || host.starts_with("127.0.0.1") { "http" } else { "https" });
+  format!("{scheme}://{host}")
+}
+
+async fn get_planet(
+  state: AppState,
+  category: Option<String>,
+  mut filters: FeedsParam,
+  headers: HeaderMap,
+  uri: Uri,
+  format: PlanetFormat,
+) -> Result<Response, Response> {
+  println!("Fetching {:?} planet for {}", format, category.as_deref().unwrap_or("all feeds"));
+
+  // A mistyped category would otherwise be an empty feed that never shows up as an error
+  if let Some(category) = &category {
+    CategoryDataSource::new(state.db.clone()).get_category_by_name(category).await
+      .map_err(|e| e.into_response())?;
+    filters.category = Some(category.clone());
+  }
+  let max_entries = filters.max_entries.unwrap_or(DEFAULT_ENTRIES).clamp(1, MAX_ENTRIES);
+  let feed_filter = filters.feed_filter();
+
+  let entry_db = EntryDataSource::new(state.db);
+  let entries = entry_db.get_timeline(&feed_filter, &filters.entry_filter(None, &SystemClock), None, max_entries as i64).await
+    .map_err(|e| e.into_response())?;
+
+  let base = public_base(SecretStore::get(&state.secrets, "PUBLIC_BASE_URL"), &headers);
+  let planet = Planet {
+    title: match &feed_filter.category {
+      Some(category) => format!("RSS Reader: {category}"),
+      None => "RSS Reader".to_string(),
+    },
+    self_url: format!("{base}{}", uri.path_and_query().map(|path| path.as_str()).unwrap_or("/")),
+    // /feeds lists the feeds collected here, the closest the service has to a site page
+    site_url: format!("{base}/feeds"),
+    entries: &entries,
+  };
+
+  Ok(match format {
+    PlanetFormat::Rss => ([(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")], write_rss(&planet)).into_response(),
+    PlanetFormat::Atom => ([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], write_atom(&planet)).into_response(),
+    PlanetFormat::JsonFeed => ([(header::CONTENT_TYPE, "application/feed+json")], write_json_feed(&planet).to_string()).into_response(),
+  })
+}
+
+// Each takes the /feeds filters, and serves a single category when routed with :name
+pub async fn get_planet_rss(
+  State(state): State<AppState>,
+  category: Option<Path<String>>,
+  Query(filters): Query<FeedsParam>,
+  headers: HeaderMap,
+  uri: Uri,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  get_planet(state, category.map(|Path(category)| category), filters, headers, uri, PlanetFormat::Rss).await
+}
+
+pub async fn get_planet_atom(
+  State(state): State<AppState>,
+  category: Option<Path<String>>,
+  Query(filters): Query<FeedsParam>,
+  headers: HeaderMap,
+  uri: Uri,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  get_planet(state, category.map(|Path(category)| category), filters, headers, uri, PlanetFormat::Atom).await
+}
+
+pub async fn get_planet_json(
+  State(state): State<AppState>,
+  category: Option<Path<String>>,
+  Query(filters): Query<FeedsParam>,
+  headers: HeaderMap,
+  uri: Uri,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  get_planet(state, category.map(|Path(category)| category), filters, headers, uri, PlanetFormat::JsonFeed).await
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+  use sqlx::types::Json;
+
+  use crate::db::{Enclosure, Entry};
+
+  use super::*;
+  use super::super::{parse_feed_xml, parser::{read_document, Element}, FeedFormat};
+
+  const SELF_URL: &str = "https://reader.example/feeds.atom";
+  const SITE_URL: &str = "https://reader.example/feeds";
+
+  fn timeline_entry(id: i32, feed_name: &str, title: &str) -> TimelineEntry {
+    TimelineEntry {
+      entry: Entry {
+        id,
+        feed_id: id * 10,
+        identity: "1".to_string(),
+        title: title.to_string(),
+        url: format!("https://example.org/{id}"),
+        created_date: Utc.with_ymd_and_hms(2024, 10, id as u32, 18, 55, 25).unwrap(),
+        updated_date: None,
+        summary: Some("<p>A <em>short</em> summary</p>".to_string()),
+        content_html: Some("<p>Fish &amp; chips <a href=\"https://example.org/\">here</a></p>".to_string()),
+        preview: Some("A short summary".to_string()),
+        authors: vec!["Pepper".to_string()],
+        categories: vec!["food".to_string()],
+        comments_url: None,
+        enclosures: Json(vec![Enclosure {
+          url: format!("https://example.org/{id}.mp3"),
+          mime_type: Some("audio/mpeg".to_string()),
+          length: Some(1234),
+          duration: Some(60),
+        }]),
+        duration: Some(60),
+        episode: None,
+        season: None,
+        image_url: None,
+        thumbnails: Vec::new(),
+      },
+      feed_name: feed_name.to_string(),
+      feed_url: format!("https://{}.example/feed.xml", feed_name.to_lowercase()),
+      category: "News".to_string(),
+    }
+  }
+
+  fn entries() -> Vec<TimelineEntry> {
+    vec![
+      timeline_entry(9, "Kitchen", "Fish & \"Chips\""),
+      // The same identity as the first, from another feed
+      timeline_entry(8, "Garden", "Tomatoes <3"),
+    ]
+  }
+
+  // RSS and Atom have nowhere to put an attachment's duration
+  fn without_durations(enclosures: &[Enclosure]) -> Vec<Enclosure> {
+    enclosures.iter().map(|enclosure| Enclosure { duration: None, ..enclosure.clone() }).collect()
+  }
+
+  fn planet(entries: &[TimelineEntry]) -> Planet<'_> {
+    Planet { title: "RSS Reader: News".to_string(), self_url: SELF_URL.to_string(), site_url: SITE_URL.to_string(), entries }
+  }
+
+  fn document(xml: &str, entry_name: &str) -> (Element, Vec<Element>) {
+    let mut entries = Vec::new();
+    let document = read_document(xml, None, entry_name, |entry| entries.push(entry)).unwrap();
+    assert!(document.warnings.is_empty(), "{:?}", document.warnings);
+    (document.root, entries)
+  }
+
+  #[test]
+  fn test_rss_round_trip() {
+    let entries = entries();
+    let xml = write_rss(&planet(&entries));
+
+    let parsed = parse_feed_xml(&xml, Some("application/rss+xml"), Some(SELF_URL)).unwrap();
+    assert_eq!(parsed.format, FeedFormat::Rss);
+    assert_eq!(parsed.title.as_deref(), Some("RSS Reader: News"));
+    assert_eq!(parsed.site_url.as_deref(), Some(SITE_URL));
+    assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+    assert_eq!(parsed.entries.len(), 2);
+
+    let first = &parsed.entries[0];
+    assert_eq!(first.identity, "urn:rss-reader:entry:9");
+    assert_ne!(first.identity, parsed.entries[1].identity);
+    assert_eq!(first.title, "Fish & \"Chips\"");
+    assert_eq!(first.url, "https://example.org/9");
+    assert_eq!(first.created_date, Some(entries[0].entry.created_date));
+    assert_eq!(first.summary.as_deref(), Some("<p>Fish &amp; chips <a href=\"https://example.org/\" rel=\"noopener noreferrer\">here</a></p>"));
+    assert_eq!(first.authors, vec!["Pepper"]);
+    assert_eq!(first.categories, vec!["food"]);
+    assert_eq!(without_durations(&first.enclosures), without_durations(&entries[0].entry.enclosures));
+
+    // Each item has what RSS 2.0 requires and credits its source feed
+    let (channel_root, items) = document(&xml, "item");
+    let channel = channel_root.child("channel").unwrap();
+    for required in ["title", "link", "description"] {
+      assert!(channel.child_text(required).is_some(), "channel {required}");
+    }
+    let source = items[1].child("source").unwrap();
+    assert_eq!(source.attribute("url"), Some("https://garden.example/feed.xml"));
+    assert_eq!(source.text().as_deref(), Some("Garden"));
+  }
+
+  #[test]
+  fn test_atom_round_trip() {
+    let entries = entries();
+    let xml = write_atom(&planet(&entries));
+
+    let parsed = parse_feed_xml(&xml, Some("application/atom+xml"), Some(SELF_URL)).unwrap();
+    assert_eq!(parsed.format, FeedFormat::Atom);
+    assert_eq!(parsed.title.as_deref(), Some("RSS Reader: News"));
+    assert_eq!(parsed.site_url.as_deref(), Some(SITE_URL));
+    assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+
+    let second = &parsed.entries[1];
+    assert_eq!(second.identity, "urn:rss-reader:entry:8");
+    assert_eq!(second.title, "Tomatoes <3");
+    assert_eq!(second.url, "https://example.org/8");
+    assert_eq!(second.created_date, Some(entries[1].entry.created_date));
+    assert_eq!(second.summary.as_deref(), Some("<p>A <em>short</em> summary</p>"));
+    assert_eq!(second.content_html, entries[1].entry.content_html.as_ref().map(|content| content.replace("\">", "\" rel=\"noopener noreferrer\">")));
+    assert_eq!(without_durations(&second.enclosures), without_durations(&entries[1].entry.enclosures));
+
+    // The elements RFC 4287 requires of a feed and its entries, and a source for each entry
+    let (feed, atom_entries) = document(&xml, "entry");
+    for required in ["id", "title", "updated", "author"] {
+      assert!(feed.child(required).is_some(), "feed {required}");
+    }
+    assert_eq!(feed.child_text("updated").as_deref(), Some("2024-10-09T18:55:25Z"));
+    for entry in &atom_entries {
+      for required in ["id", "title", "updated"] {
+        assert!(entry.child(required).is_some(), "entry {required}");
+      }
+    }
+    let source = atom_entries[0].child("source").unwrap();
+    assert_eq!(source.child_text("title").as_deref(), Some("Kitchen"));
+    assert_eq!(source.child_text("id").as_deref(), Some("https://kitchen.example/feed.xml"));
+  }
+
+  #[test]
+  fn test_json_feed_round_trip() {
+    let mut entries = entries();
+    entries[0].entry.enclosures.0.push(Enclosure {
+      url: "https://example.org/9.txt".to_string(),
+      mime_type: Some("text/plain".to_string()),
+      length: None,
+      duration: None,
+    });
+    let json = write_json_feed(&planet(&entries)).to_string();
+
+    let parsed = parse_feed_xml(&json, Some("application/feed+json"), Some(SELF_URL)).unwrap();
+    assert_eq!(parsed.format, FeedFormat::JsonFeed);
+    assert_eq!(parsed.title.as_deref(), Some("RSS Reader: News"));
+    assert_eq!(parsed.site_url.as_deref(), Some(SITE_URL));
+    assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+
+    let first = &parsed.entries[0];
+    assert_eq!(first.identity, "urn:rss-reader:entry:9");
+    assert_eq!(first.title, "Fish & \"Chips\"");
+    assert_eq!(first.created_date, Some(entries[0].entry.created_date));
+    assert_eq!(first.summary.as_deref(), Some("A short summary"));
+    assert_eq!(first.authors, vec!["Pepper"]);
+    assert_eq!(first.categories, vec!["food"]);
+    assert_eq!(first.enclosures, entries[0].entry.enclosures.0);
+    assert_eq!(first.duration, Some(60));
+
+    let value: Value = serde_json::from_str(&json).unwrap();
+    // Only the attachment the duration belongs to carries it
+    assert_eq!(value["items"][0]["attachments"][0]["duration_in_seconds"], 60);
+    assert_eq!(value["items"][0]["attachments"][1]["duration_in_seconds"], Value::Null);
+    assert_eq!(value["items"][1]["_source"]["title"], "Garden");
+    assert_eq!(value["items"][1]["_source"]["feed_url"], "https://garden.example/feed.xml");
+  }
+
+  #[test]
+  fn test_empty_planet() {
+    let planet = planet(&[]);
+    assert!(parse_feed_xml(&write_rss(&planet), None, None).unwrap().entries.is_empty());
+    assert!(parse_feed_xml(&write_atom(&planet), None, None).unwrap().entries.is_empty());
+    assert!(parse_feed_xml(&write_json_feed(&planet).to_string(), None, None).unwrap().entries.is_empty());
+  }
+
+  #[test]
+  fn test_public_base() {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::HOST, "evil.example".parse().unwrap());
+    headers.insert("x-forwarded-proto", "http".parse().unwrap());
+    assert_eq!(public_base(Some("https://reader.example/".to_string()), &headers), "https://reader.example");
+    assert_eq!(public_base(None, &headers), "http://evil.example");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::HOST, "reader.example".parse().unwrap());
+    assert_eq!(public_base(None, &headers), "https://reader.example");
+
+    headers.insert(header::HOST, "localhost:8000".parse().unwrap());
+    assert_eq!(public_base(None, &headers), "http://localhost:8000");
+  }
+
+  // Run against a scratch database with DATABASE_URL=postgres://... cargo test -- --ignored
+  #[sqlx::test]
+  #[ignore = "needs a Postgres DATABASE_URL"]
+  async fn test_unknown_category_planet_not_found(db: sqlx::PgPool) {
+    use axum::http::StatusCode;
+
+    use crate::db::CategoryInput;
+
+    CategoryDataSource::new(db.clone()).create_category(CategoryInput { name: "Tech".to_string() }).await.unwrap();
+    let state = AppState {
+      db,
+      secrets: shuttle_runtime::SecretStore::new(Default::default()),
+      client: reqwest::Client::new(),
+    };
+
+    let response = get_planet(state.clone(), Some("Tech".to_string()), FeedsParam::default(), HeaderMap::new(), "/categories/Tech/feed.rss".parse().unwrap(), PlanetFormat::Rss).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = get_planet(state, Some("Tehc".to_string()), FeedsParam::default(), HeaderMap::new(), "/categories/Tehc/feed.rss".parse().unwrap(), PlanetFormat::Rss).await.unwrap_err();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  }
+}
@@ -0,0 +1,204 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use super::{links::resolve_xml_base, media::Media, parser::{read_document, Element, XmlError}, rss::{distinct, number, update_period_secs}, FeedDate};
+
+#[derive(Serialize, Debug)]
+pub enum RDFError {
+  Message(String),
+}
+
+impl Display for RDFError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RDFError::Message(msg) => write!(f, "RDF: {}", msg),
+    }
+  }
+}
+
+impl From<XmlError> for RDFError {
+  fn from(e: XmlError) -> Self {
+    RDFError::Message(e.to_string())
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RDFItem {
+  // xml:base in effect for the item
+  pub base: Option<String>,
+  pub about: Option<String>,
+  pub link: Option<String>,
+  pub date: Option<FeedDate>,
+  pub modified: Option<FeedDate>,
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub content_encoded: Option<String>,
+  pub creators: Vec<String>,
+  pub subjects: Vec<String>,
+  pub media: Media,
+}
+
+impl RDFItem {
+  fn from_element(item: &Element) -> Self {
+    Self {
+      base: item.base.clone(),
+      about: item.attribute("rdf:about").map(|about| about.to_string()),
+      link: item.child_text("link"),
+      date: item.child_text("dc:date").map(FeedDate::from),
+      modified: item.child_text("dcterms:modified").map(FeedDate::from),
+      title: item.child_text("title"),
+      description: item.child_text("description"),
+      content_encoded: item.child_text("content:encoded"),
+      creators: distinct(item.children("dc:creator").filter_map(Element::text)),
+      subjects: distinct(item.children("dc:subject").filter_map(Element::text)),
+      media: Media::from_element(item, Vec::new()),
+    }
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RDFChannel {
+  pub title: Option<String>,
+  // The site the feed belongs to
+  pub link: Option<String>,
+  pub description: Option<String>,
+  // The <image> beside the channel, like the items are
+  pub image: Option<String>,
+  pub update_period: Option<String>,
+  pub update_frequency: Option<i64>,
+}
+
+impl RDFChannel {
+  // Publisher's suggested polling interval in seconds, from <sy:updatePeriod>
+  pub fn refresh_hint(&self) -> Option<i64> {
+    self.update_period.as_deref()
+      .and_then(|period| update_period_secs(period, self.update_frequency))
+  }
+}
+
+// RSS 1.0 keeps <item> elements as siblings of <channel> rather than its children
+#[derive(Serialize, Debug)]
+pub struct RDFFeed {
+  pub channel: RDFChannel,
+  pub item: Vec<RDFItem>,
+  // Problems with the document itself, like markup that broke partway through
+  pub warnings: Vec<String>,
+}
+
+pub fn read_rdf(xml: &str, feed_url: Option<&str>) -> Result<RDFFeed, RDFError> {
+  let mut item = Vec::new();
+  let document = read_document(xml, feed_url, "item", |element| item.push(RDFItem::from_element(&element)))?;
+
+  if document.root.name != "rdf:RDF" {
+    return Err(RDFError::Message(format!("Expected <rdf:RDF> but found <{}>", document.root.name)));
+  }
+  let channel = document.root.child("channel")
+    .ok_or_else(|| RDFError::Message("Missing <channel>".to_string()))?;
+
+  Ok(RDFFeed {
+    channel: RDFChannel {
+      title: channel.child_text("title"),
+      link: channel.child_text("link").map(|link| resolve_xml_base(channel.base.as_deref(), &link)),
+      description: channel.child_text("description"),
+      image: document.root.child("image").and_then(|image| image.child_text("url"))
+        .map(|image| resolve_xml_base(document.root.base.as_deref(), &image)),
+      update_period: channel.child_text("sy:updatePeriod"),
+      update_frequency: number(channel.child_text("sy:updateFrequency")),
+    },
+    item,
+    warnings: document.warnings,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rdf_with(channel: &str, items: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+      <rdf:RDF
+        xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+        xmlns="http://purl.org/rss/1.0/"
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">
+        <channel rdf:about="https://arxiv.org/">
+          <title>arXiv</title>
+          <link>https://arxiv.org/</link>
+          {channel}
+        </channel>
+        {items}
+      </rdf:RDF>"#)
+  }
+
+  fn assert_rdf_feed_parsed(result: Result<RDFFeed, RDFError>) {
+    match result {
+      Ok(feed) => {
+        assert!(!feed.item.is_empty(), "Expected at least one item");
+
+        let first_item = &feed.item[0];
+        assert_eq!(first_item.about.as_deref(), Some("https://arxiv.org/abs/2410.07095"));
+        assert_eq!(first_item.link.as_deref(), Some("https://arxiv.org/abs/2410.07095"));
+        assert_eq!(first_item.title.as_deref(), Some("Scaling Laws for Terrible Video Games"));
+        assert_eq!(first_item.date.as_ref().and_then(FeedDate::parsed).unwrap().to_string(), "2024-10-09 18:55:25 UTC");
+      }
+      Err(e) => panic!("Parsing failed: {:?}", e),
+    }
+  }
+
+  #[test]
+  fn test_read_rdf() {
+    let xml = rdf_with("", r#"
+      <item rdf:about="https://arxiv.org/abs/2410.07095">
+        <title>Scaling Laws for Terrible Video Games</title>
+        <link>https://arxiv.org/abs/2410.07095</link>
+        <dc:date>2024-10-09T18:55:25+00:00</dc:date>
+        <dc:creator>Pepper, T.</dc:creator>
+        <dc:subject>cs.LG</dc:subject>
+        <description>We find that games get worse with scale.</description>
+      </item>"#);
+
+    let result = read_rdf(&xml, None);
+    assert_rdf_feed_parsed(result);
+
+    let feed = read_rdf(&xml, None).unwrap();
+    assert_eq!(feed.item[0].creators, vec!["Pepper, T.".to_string()]);
+    assert_eq!(feed.item[0].subjects, vec!["cs.LG".to_string()]);
+    assert_eq!(feed.item[0].description.as_deref(), Some("We find that games get worse with scale."));
+  }
+
+  #[test]
+  fn test_read_rdf_date_only() {
+    let xml = rdf_with("", r#"
+      <item rdf:about="https://arxiv.org/abs/2410.07095">
+        <title>Scaling Laws for Terrible Video Games</title>
+        <link>https://arxiv.org/abs/2410.07095</link>
+        <dc:date>2024-10-09</dc:date>
+      </item>"#);
+
+    let feed = read_rdf(&xml, None).unwrap();
+    assert_eq!(feed.item[0].date.as_ref().and_then(FeedDate::parsed).unwrap().to_string(), "2024-10-09 00:00:00 UTC");
+  }
+
+  #[test]
+  fn test_read_rdf_no_about_or_date() {
+    let xml = rdf_with("", r#"
+      <item>
+        <title>Scaling Laws for Terrible Video Games</title>
+        <link>https://arxiv.org/abs/2410.07095</link>
+      </item>"#);
+
+    let feed = read_rdf(&xml, None).unwrap();
+    assert_eq!(feed.item[0].about, None);
+    assert_eq!(feed.item[0].date, None);
+  }
+
+  #[test]
+  fn test_rdf_refresh_hint() {
+    let xml = rdf_with("<sy:updatePeriod>hourly</sy:updatePeriod><sy:updateFrequency>2</sy:updateFrequency>", "");
+
+    let feed = read_rdf(&xml, None).unwrap();
+    assert!(feed.item.is_empty());
+    assert_eq!(feed.channel.refresh_hint(), Some(1800));
+  }
+}
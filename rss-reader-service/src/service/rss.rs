@@ -1,10 +1,10 @@
 use std::fmt::Display;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
-use serde::{de, Deserialize, Deserializer, Serialize};
-use serde_json::{from_value, Value};
+use serde::Serialize;
 
-#[derive(Deserialize, Serialize, Debug)]
+use super::{links::resolve_xml_base, media::Media, parser::{read_document, Element, XmlError}, FeedDate};
+
+#[derive(Serialize, Debug)]
 pub enum RSSError {
   Message(String),
 }
@@ -13,54 +13,329 @@ impl Display for RSSError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       RSSError::Message(msg) => write!(f, "RSS: {}", msg),
-    }    
+    }
+  }
+}
+
+impl From<XmlError> for RSSError {
+  fn from(e: XmlError) -> Self {
+    RSSError::Message(e.to_string())
   }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Serialize, Debug)]
 pub struct RSSItem {
-  pub link: String,
-  #[serde(rename = "pubDate", deserialize_with = "updated_date_time")]
-  pub pub_date: DateTime<Utc>,
-  pub title: String
+  // xml:base in effect for the item
+  pub base: Option<String>,
+  pub guid: Option<String>,
+  pub link: Option<String>,
+  pub pub_date: Option<FeedDate>,
+  pub updated: Option<FeedDate>,
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub content_encoded: Option<String>,
+  pub authors: Vec<String>,
+  pub categories: Vec<String>,
+  pub comments: Option<String>,
+  pub media: Media,
+}
+
+impl RSSItem {
+  fn from_element(item: &Element) -> Self {
+    let authors = item.children("author")
+      .filter_map(Element::text)
+      .map(|author| author_name(&author))
+      .chain(item.children("dc:creator").filter_map(Element::text));
+
+    Self {
+      base: item.base.clone(),
+      guid: item.child_text("guid"),
+      link: item.child_text("link"),
+      pub_date: item.child_text("pubDate")
+        .or_else(|| item.child_text("dc:date"))
+        .map(FeedDate::from),
+      updated: item.child_text("atom:updated")
+        .or_else(|| item.child_text("dcterms:modified"))
+        .map(FeedDate::from),
+      title: item.child_text("title"),
+      description: item.child_text("description"),
+      content_encoded: item.child_text("content:encoded"),
+      authors: distinct(authors),
+      categories: distinct(item.children("category").chain(item.children("dc:subject")).filter_map(Element::text)),
+      comments: item.child_text("comments"),
+      media: Media::from_element(item, Vec::new()),
+    }
+  }
+}
+
+// <author> is meant to hold an email address, usually written as "email (Name)"
+fn author_name(author: &str) -> String {
+  match author.split_once('(') {
+    Some((_, name)) if author.ends_with(')') => name.trim_end_matches(')').trim().to_string(),
+    _ => author.to_string(),
+  }
+}
+
+// Values in document order without repeats
+pub(super) fn distinct(values: impl Iterator<Item = String>) -> Vec<String> {
+  let mut distinct: Vec<String> = Vec::new();
+  for value in values {
+    if !distinct.contains(&value) {
+      distinct.push(value);
+    }
+  }
+  distinct
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Serialize, Debug)]
 pub struct RSSChannel {
-  pub item: Vec<RSSItem>
+  pub item: Vec<RSSItem>,
+  pub title: Option<String>,
+  // The site the feed belongs to
+  pub link: Option<String>,
+  pub description: Option<String>,
+  // <image><url>, or the podcast artwork
+  pub image: Option<String>,
+  pub ttl: Option<i64>,
+  pub update_period: Option<String>,
+  pub update_frequency: Option<i64>,
+  // Problems with the document itself, like markup that broke partway through
+  pub warnings: Vec<String>,
+}
+
+impl RSSChannel {
+  // Publisher's suggested polling interval in seconds, from <ttl> or <sy:updatePeriod>
+  pub fn refresh_hint(&self) -> Option<i64> {
+    let ttl = self.ttl.filter(|ttl| *ttl > 0).map(|ttl| ttl * 60);
+
+    let update_period = self.update_period.as_deref()
+      .and_then(|period| update_period_secs(period, self.update_frequency));
+
+    ttl.max(update_period)
+  }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct RSSRoot {
-  pub channel: RSSChannel
+// Seconds between updates announced by the syndication module's <sy:updatePeriod>
+pub(super) fn update_period_secs(period: &str, frequency: Option<i64>) -> Option<i64> {
+  let period_secs = match period.trim() {
+    "hourly" => 60 * 60,
+    "daily" => 60 * 60 * 24,
+    "weekly" => 60 * 60 * 24 * 7,
+    "monthly" => 60 * 60 * 24 * 30,
+    "yearly" => 60 * 60 * 24 * 365,
+    _ => return None,
+  };
+  let frequency = frequency.filter(|f| *f > 0).unwrap_or(1);
+  Some(period_secs / frequency)
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct RSSObject {
-  pub rss: RSSRoot
+pub(super) fn number(text: Option<String>) -> Option<i64> {
+  text.and_then(|number| number.trim().parse().ok())
 }
 
-fn updated_date_time<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
-where
-  D: Deserializer<'de>,
-{
-  let s = String::deserialize(deserializer)?;
-
-  // Define multiple date formats
-  let formats = [
-    "%a, %d %b %Y %H:%M:%S %z",  // Example: Wed, 11 Sep 2024 00:00:00 -0400
-    "%a, %d %b %Y %H:%M:%S GMT", // Example: Tue, 03 Sep 2024 13:51:48 GMT
-  ];
-
-  for format in &formats {
-    if let Ok(dt) = NaiveDateTime::parse_from_str(&s, format) {
-      return Ok(dt.and_utc());
-    }
+pub fn read_rss(xml: &str, feed_url: Option<&str>) -> Result<RSSChannel, RSSError> {
+  let mut item = Vec::new();
+  let document = read_document(xml, feed_url, "item", |element| item.push(RSSItem::from_element(&element)))?;
+
+  if document.root.name != "rss" {
+    return Err(RSSError::Message(format!("Expected <rss> but found <{}>", document.root.name)));
+  }
+  let channel = document.root.child("channel")
+    .ok_or_else(|| RSSError::Message("Missing <channel>".to_string()))?;
+
+  Ok(RSSChannel {
+    item,
+    title: channel.child_text("title"),
+    link: channel.child_text("link").map(|link| resolve_xml_base(channel.base.as_deref(), &link)),
+    description: channel.child_text("description"),
+    image: channel.child("image").and_then(|image| image.child_text("url"))
+      .or_else(|| channel.child("itunes:image").and_then(|image| image.attribute("href")).map(String::from))
+      .map(|image| resolve_xml_base(channel.base.as_deref(), image.trim())),
+    ttl: number(channel.child_text("ttl")),
+    update_period: channel.child_text("sy:updatePeriod"),
+    update_frequency: number(channel.child_text("sy:updateFrequency")),
+    warnings: document.warnings,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::db::Enclosure;
+
+  use super::*;
+
+  fn rss_with_guid(guid: &str) -> String {
+    rss_with_channel_hints(guid, "")
   }
 
-  Err(de::Error::custom(&format!("Failed to parse RSS date: {}", &s)))
+  fn rss_with_channel_hints(guid: &str, hints: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+      <rss version="2.0" xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">
+        <channel>
+          <title>This Week in Rust</title>
+          {hints}
+          <item>
+            {guid}
+            <link>https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/</link>
+            <pubDate>Wed, 09 Oct 2024 00:00:00 -0400</pubDate>
+            <title>This Week in Rust 568</title>
+          </item>
+        </channel>
+      </rss>"#)
+  }
+
+  #[test]
+  fn test_read_rss() {
+    let result = read_rss(&rss_with_guid("<guid>twir-568</guid>"), None).unwrap();
+    let item = &result.item[0];
+    assert_eq!(item.link.as_deref(), Some("https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/"));
+    assert_eq!(item.title.as_deref(), Some("This Week in Rust 568"));
+    assert_eq!(item.pub_date.as_ref().and_then(FeedDate::parsed).unwrap().to_string(), "2024-10-09 04:00:00 UTC");
+  }
+
+  #[test]
+  fn test_read_rss_details() {
+    let xml = r#"<rss version="2.0"
+        xmlns:content="http://purl.org/rss/1.0/modules/content/"
+        xmlns:dc="http://purl.org/dc/elements/1.1/">
+        <channel>
+          <item>
+            <link>https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/</link>
+            <description>&lt;p&gt;Hello &amp;amp; welcome&lt;/p&gt;</description>
+            <content:encoded><![CDATA[<p>The whole issue</p>]]></content:encoded>
+            <author>editors@this-week-in-rust.org (TWiR Contributors)</author>
+            <dc:creator>Nellie</dc:creator>
+            <dc:creator>TWiR Contributors</dc:creator>
+            <category>Rust</category>
+            <category domain="https://example.org/tags">Newsletter</category>
+            <dc:subject>Rust</dc:subject>
+            <comments>https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/#comments</comments>
+          </item>
+        </channel>
+      </rss>"#;
+
+    let result = read_rss(xml, None).unwrap();
+    let item = &result.item[0];
+    assert_eq!(item.description.as_deref(), Some("<p>Hello &amp; welcome</p>"));
+    assert_eq!(item.content_encoded.as_deref(), Some("<p>The whole issue</p>"));
+    assert_eq!(item.authors, vec!["TWiR Contributors".to_string(), "Nellie".to_string()]);
+    assert_eq!(item.categories, vec!["Rust".to_string(), "Newsletter".to_string()]);
+    assert_eq!(item.comments.as_deref(), Some("https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/#comments"));
+  }
+
+  #[test]
+  fn test_read_rss_podcast() {
+    let xml = r#"<rss version="2.0"
+        xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"
+        xmlns:media="http://search.yahoo.com/mrss/">
+        <channel>
+          <item>
+            <title>Episode 12: Terrible Games</title>
+            <link>https://example.org/episodes/12</link>
+            <enclosure url="https://cdn.example.org/12.mp3" length="31337000" type="audio/mpeg" />
+            <itunes:duration>1:02:03</itunes:duration>
+            <itunes:episode>12</itunes:episode>
+            <itunes:season>2</itunes:season>
+            <itunes:image href="https://cdn.example.org/12.jpg" />
+            <media:content url="https://cdn.example.org/12.mp3" type="audio/mpeg" />
+            <media:content url="https://cdn.example.org/12.ogg" type="audio/ogg" fileSize="0">
+              <media:thumbnail url="https://cdn.example.org/12-thumb.jpg" />
+            </media:content>
+          </item>
+          <item>
+            <link>https://example.org/posts/1</link>
+            <enclosure type="audio/mpeg" />
+          </item>
+        </channel>
+      </rss>"#;
+
+    let result = read_rss(xml, None).unwrap();
+    let media = &result.item[0].media;
+    assert_eq!(media.enclosures, vec![
+      Enclosure { url: "https://cdn.example.org/12.mp3".to_string(), mime_type: Some("audio/mpeg".to_string()), length: Some(31337000), duration: Some(3723) },
+      Enclosure { url: "https://cdn.example.org/12.ogg".to_string(), mime_type: Some("audio/ogg".to_string()), length: None, duration: None },
+    ]);
+    assert_eq!(media.duration, Some(3723));
+    assert_eq!(media.episode, Some(12));
+    assert_eq!(media.season, Some(2));
+    assert_eq!(media.image_url.as_deref(), Some("https://cdn.example.org/12.jpg"));
+    assert_eq!(media.thumbnails, vec!["https://cdn.example.org/12-thumb.jpg".to_string()]);
+    assert!(result.item[1].media.enclosures.is_empty());
+  }
+
+  #[test]
+  fn test_read_rss_guid_string() {
+    let result = read_rss(&rss_with_guid("<guid>twir-568</guid>"), None).unwrap();
+    assert_eq!(result.item[0].guid, Some("twir-568".to_string()));
+  }
+
+  #[test]
+  fn test_read_rss_guid_attributes() {
+    let result = read_rss(&rss_with_guid(r#"<guid isPermaLink="false">twir-568</guid>"#), None).unwrap();
+    assert_eq!(result.item[0].guid, Some("twir-568".to_string()));
+  }
+
+  #[test]
+  fn test_read_rss_guid_number() {
+    let result = read_rss(&rss_with_guid("<guid>568</guid>"), None).unwrap();
+    assert_eq!(result.item[0].guid, Some("568".to_string()));
+  }
+
+  #[test]
+  fn test_read_rss_no_guid() {
+    let result = read_rss(&rss_with_guid(""), None).unwrap();
+    assert_eq!(result.item[0].guid, None);
+  }
+
+  #[test]
+  fn test_rss_refresh_hint_ttl() {
+    let result = read_rss(&rss_with_channel_hints("", "<ttl>60</ttl>"), None).unwrap();
+    assert_eq!(result.refresh_hint(), Some(3600));
+  }
+
+  #[test]
+  fn test_rss_refresh_hint_update_period() {
+    let hints = "<sy:updatePeriod>daily</sy:updatePeriod><sy:updateFrequency>2</sy:updateFrequency>";
+    let result = read_rss(&rss_with_channel_hints("", hints), None).unwrap();
+    assert_eq!(result.refresh_hint(), Some(43200));
+  }
+
+  #[test]
+  fn test_rss_refresh_hint_prefers_longest() {
+    let hints = "<ttl> 30 </ttl><sy:updatePeriod>hourly</sy:updatePeriod>";
+    let result = read_rss(&rss_with_channel_hints("", hints), None).unwrap();
+    assert_eq!(result.refresh_hint(), Some(3600));
+  }
+
+  #[test]
+  fn test_rss_refresh_hint_none() {
+    let result = read_rss(&rss_with_guid(""), None).unwrap();
+    assert_eq!(result.refresh_hint(), None);
+  }
+
+  #[test]
+  fn test_read_rss_partial_items() {
+    let xml = r#"<rss version="2.0"><channel>
+        <item>
+          <description>A link-less note</description>
+          <pubDate>Wed, 09 Oct 2024 00:00:00 -0400</pubDate>
+        </item>
+        <item>
+          <link>https://example.org/2</link>
+          <title></title>
+        </item>
+      </channel></rss>"#;
+
+    let result = read_rss(xml, None).unwrap();
+    assert_eq!(result.item.len(), 2);
+    assert_eq!(result.item[0].link, None);
+    assert_eq!(result.item[0].description.as_deref(), Some("A link-less note"));
+    assert_eq!(result.item[1].title, None);
+  }
+
+  #[test]
+  fn test_read_rss_wrong_root() {
+    assert!(read_rss("<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>", None).is_err());
+    assert!(read_rss("<rss version=\"2.0\"></rss>", None).is_err());
+  }
 }
-               
-pub fn rss_to_json(value: Value) -> Result<RSSObject, RSSError> {
-  from_value(value).map_err(|e| RSSError::Message(e.to_string()))
-}
\ No newline at end of file
@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet};
+
+use ammonia::{Builder, UrlRelative};
+use url::Url;
+
+// Markup a reader can render without trusting the publisher
+const TAGS: &[&str] = &[
+  "a", "abbr", "b", "blockquote", "br", "caption", "cite", "code", "dd", "del", "details", "dl", "dt",
+  "em", "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img", "ins", "kbd",
+  "li", "mark", "ol", "p", "pre", "q", "s", "small", "span", "strong", "sub", "summary", "sup",
+  "table", "tbody", "td", "tfoot", "th", "thead", "time", "tr", "u", "ul",
+];
+
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+  ("a", &["href", "title"]),
+  ("abbr", &["title"]),
+  ("blockquote", &["cite"]),
+  ("del", &["cite", "datetime"]),
+  ("img", &["src", "alt", "title", "width", "height"]),
+  ("ins", &["cite", "datetime"]),
+  ("ol", &["start"]),
+  ("q", &["cite"]),
+  ("td", &["colspan", "rowspan"]),
+  ("th", &["colspan", "rowspan", "scope"]),
+  ("time", &["datetime"]),
+];
+
+const URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+// Elements whose text is code or chrome rather than something to read
+const DROPPED_CONTENT: &[&str] = &["script", "style", "noscript", "template", "iframe", "object"];
+
+// Elements that separate words when markup is flattened to text
+const BLOCK_TAGS: &[&str] = &[
+  "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "figure",
+  "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "ol", "p", "pre", "section",
+  "table", "td", "th", "tr", "ul",
+];
+
+// Hosts that only serve analytics beacons and share buttons
+const TRACKER_HOSTS: &[&str] = &[
+  "feeds.feedburner.com",
+  "feedproxy.google.com",
+  "feeds.feedblitz.com",
+  "pixel.wp.com",
+  "stats.wordpress.com",
+  "www.google-analytics.com",
+  "pixel.quantserve.com",
+  "counter.theconversation.com",
+];
+
+const PREVIEW_LENGTH: usize = 280;
+
+// Cleans publisher HTML, resolving relative links against one base URL
+pub struct Sanitizer {
+  html: Builder<'static>,
+  text: Builder<'static>,
+}
+
+impl Sanitizer {
+  pub fn new(base_url: Option<&Url>) -> Self {
+    let mut html = Builder::empty();
+    html.tags(TAGS.iter().copied().collect())
+      .tag_attributes(TAG_ATTRIBUTES.iter().map(|(tag, attributes)| (*tag, attributes.iter().copied().collect())).collect())
+      .url_schemes(URL_SCHEMES.iter().copied().collect())
+      .clean_content_tags(DROPPED_CONTENT.iter().copied().collect())
+      .link_rel(Some("noopener noreferrer"))
+      .attribute_filter(|element, attribute, value| {
+        if element == "img" && attribute == "src" && is_tracker(value) {
+          return None;
+        }
+        Some(value.into())
+      })
+      // Relative links can't be resolved from the reader's own origin, so without a base they go
+      .url_relative(match base_url {
+        Some(base) => UrlRelative::RewriteWithBase(base.clone()),
+        None => UrlRelative::Deny,
+      });
+
+    let mut text = Builder::empty();
+    text.tags(BLOCK_TAGS.iter().copied().collect())
+      .tag_attributes(HashMap::new())
+      .generic_attributes(HashSet::new())
+      .clean_content_tags(DROPPED_CONTENT.iter().copied().collect())
+      .link_rel(None);
+
+    Self { html, text }
+  }
+
+  // HTML safe to render as-is, or None when nothing is left of it
+  pub fn safe_html(&self, html: &str) -> Option<String> {
+    let cleaned = drop_tracking_pixels(&self.html.clean(html).to_string());
+    let cleaned = cleaned.trim();
+    (!cleaned.is_empty()).then(|| cleaned.to_string())
+  }
+
+  // Text with markup removed, entities decoded and whitespace collapsed, for titles and previews
+  pub fn plain_text(&self, html: &str) -> Option<String> {
+    let cleaned = self.text.clean(html).to_string();
+
+    // Only the block tags survive cleaning, and literal brackets in the text come out escaped
+    let mut text = String::with_capacity(cleaned.len());
+    let mut in_tag = false;
+    for c in cleaned.chars() {
+      match c {
+        '<' => in_tag = true,
+        '>' if in_tag => {
+          in_tag = false;
+          text.push(' ');
+        },
+        _ if !in_tag => text.push(c),
+        _ => {},
+      }
+    }
+
+    let text = unescape(&text).split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+  }
+
+  // The opening of an entry's text, cut at a word boundary
+  pub fn preview(&self, html: &str) -> Option<String> {
+    let text = self.plain_text(html)?;
+    if text.chars().count() <= PREVIEW_LENGTH {
+      return Some(text);
+    }
+
+    let cut: String = text.chars().take(PREVIEW_LENGTH).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(words, _)| words);
+    Some(format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation())))
+  }
+}
+
+// A sanitizer for each base URL in a feed, since entries can set their own with xml:base
+#[derive(Default)]
+pub struct Sanitizers(HashMap<Option<Url>, Sanitizer>);
+
+impl Sanitizers {
+  pub fn for_base(&mut self, base_url: Option<&Url>) -> &Sanitizer {
+    self.0.entry(base_url.cloned()).or_insert_with(|| Sanitizer::new(base_url))
+  }
+}
+
+fn is_tracker(src: &str) -> bool {
+  Url::parse(src).ok()
+    .and_then(|url| url.host_str().map(|host| TRACKER_HOSTS.contains(&host)))
+    .unwrap_or(false)
+}
+
+// Drops images no reader would see: 1x1 beacons and ones whose source was removed. The input is
+// ammonia's output, where attribute values are always double quoted and text never holds a raw <
+fn drop_tracking_pixels(html: &str) -> String {
+  let mut result = String::with_capacity(html.len());
+  let mut rest = html;
+
+  while let Some(start) = rest.find("<img") {
+    result.push_str(&rest[..start]);
+    let tag_and_rest = &rest[start..];
+
+    let mut in_quotes = false;
+    let end = tag_and_rest.char_indices()
+      .find(|(_, c)| {
+        if *c == '"' {
+          in_quotes = !in_quotes;
+        }
+        *c == '>' && !in_quotes
+      })
+      .map_or(tag_and_rest.len(), |(i, _)| i + 1);
+
+    let tag = &tag_and_rest[..end];
+    if !is_tracking_pixel(tag) {
+      result.push_str(tag);
+    }
+    rest = &tag_and_rest[end..];
+  }
+
+  result.push_str(rest);
+  result
+}
+
+fn is_tracking_pixel(tag: &str) -> bool {
+  let tiny = |attribute: &str| ["0", "1", "0px", "1px"].iter()
+    .any(|size| tag.contains(&format!(" {attribute}=\"{size}\"")));
+
+  !tag.contains(" src=\"") || tiny("width") || tiny("height")
+}
+
+fn unescape(text: &str) -> String {
+  text.replace("&nbsp;", " ")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn base_url() -> Url {
+    Url::parse("https://example.org/blog/post.html").unwrap()
+  }
+
+  // (name, publisher HTML, what's safe to render)
+  const MALICIOUS_HTML: &[(&str, &str, &str)] = &[
+    ("script tag", r#"<p>Hi</p><script>alert(document.cookie)</script>"#, "<p>Hi</p>"),
+    ("script in svg", r#"<svg><script>alert(1)</script></svg><p>Hi</p>"#, "<p>Hi</p>"),
+    ("event handler", r#"<img src="/a.png" onerror="alert(1)" alt="A">"#, r#"<img src="https://example.org/a.png" alt="A">"#),
+    ("javascript link", r#"<a href="javascript:alert(1)">Click</a>"#, r#"<a rel="noopener noreferrer">Click</a>"#),
+    ("obfuscated javascript link", r#"<a href="jav&#x09;ascript:alert(1)">Click</a>"#, r#"<a rel="noopener noreferrer">Click</a>"#),
+    ("data uri", r#"<img src="data:image/svg+xml;base64,PHN2Zz4=" alt="x">"#, ""),
+    ("style attribute", r#"<p style="background:url(javascript:alert(1))">Hi</p>"#, "<p>Hi</p>"),
+    ("style element", r#"<style>body { display: none }</style><p>Hi</p>"#, "<p>Hi</p>"),
+    ("iframe", r#"<iframe src="https://evil.example/"></iframe><p>Hi</p>"#, "<p>Hi</p>"),
+    ("form", r#"<form action="https://evil.example/"><input name="password"></form>"#, ""),
+    ("meta refresh", r#"<meta http-equiv="refresh" content="0;url=https://evil.example/"><p>Hi</p>"#, "<p>Hi</p>"),
+    ("unclosed markup", r#"<p>Hi <b>there"#, "<p>Hi <b>there</b></p>"),
+    ("comment", r#"<p>Hi</p><!--[if IE]><script>alert(1)</script><![endif]-->"#, "<p>Hi</p>"),
+    ("tracking pixel", r#"<p>Hi</p><img src="https://example.net/open.gif" width="1" height="1">"#, "<p>Hi</p>"),
+    ("tracker host", r#"<p>Hi</p><img src="https://feeds.feedburner.com/~r/example/~4/abc" alt="">"#, "<p>Hi</p>"),
+    ("relative links", r#"<a href="../about">About</a> <img src="img/cat.png" alt="Cat">"#,
+      r#"<a href="https://example.org/about" rel="noopener noreferrer">About</a> <img src="https://example.org/blog/img/cat.png" alt="Cat">"#),
+    ("protocol relative", r#"<img src="//cdn.example.org/cat.png" alt="Cat">"#, r#"<img src="https://cdn.example.org/cat.png" alt="Cat">"#),
+  ];
+
+  #[test]
+  fn test_safe_html_fixtures() {
+    let sanitizer = Sanitizer::new(Some(&base_url()));
+
+    for (name, html, expected) in MALICIOUS_HTML {
+      assert_eq!(sanitizer.safe_html(html).unwrap_or_default(), *expected, "{name}");
+    }
+  }
+
+  #[test]
+  fn test_safe_html_without_base() {
+    let sanitizer = Sanitizer::new(None);
+    assert_eq!(sanitizer.safe_html(r#"<a href="/about">About</a>"#).as_deref(), Some(r#"<a rel="noopener noreferrer">About</a>"#));
+    assert_eq!(sanitizer.safe_html(r#"<a href="https://example.org/">Home</a>"#).as_deref(),
+      Some(r#"<a href="https://example.org/" rel="noopener noreferrer">Home</a>"#));
+  }
+
+  #[test]
+  fn test_plain_text() {
+    let sanitizer = Sanitizer::new(Some(&base_url()));
+    assert_eq!(sanitizer.plain_text("I Made a <em>Terrible</em> Video Game").as_deref(), Some("I Made a Terrible Video Game"));
+    assert_eq!(sanitizer.plain_text("<p>One</p><p>Two<br>Three</p>").as_deref(), Some("One Two Three"));
+    assert_eq!(sanitizer.plain_text("Fish &amp; chips &lt;3&nbsp;").as_deref(), Some("Fish & chips <3"));
+    assert_eq!(sanitizer.plain_text("AT&T").as_deref(), Some("AT&T"));
+    assert_eq!(sanitizer.plain_text("Hi<script>alert('<p>')</script>").as_deref(), Some("Hi"));
+    assert_eq!(sanitizer.plain_text("<img src=x onerror=alert(1)>"), None);
+  }
+
+  #[test]
+  fn test_preview() {
+    let sanitizer = Sanitizer::new(None);
+    let long = format!("<p>{}</p>", "word, ".repeat(100));
+
+    let preview = sanitizer.preview(&long).unwrap();
+    assert!(preview.chars().count() <= PREVIEW_LENGTH + 1);
+    assert!(preview.ends_with("word…"));
+    assert_eq!(sanitizer.preview("<p>Short</p>").as_deref(), Some("Short"));
+  }
+}
@@ -0,0 +1,156 @@
+use std::fmt::Write;
+
+use axum::{extract::{Query, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{db::{EntryDataSource, EntryFilter, FeedFilter, TimelineEntry}, AppState};
+
+use super::{Entry, FeedsParam, SystemClock};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+// Position after the last entry of a page. Clients get it hex encoded and pass it back as is.
+#[derive(Debug, PartialEq)]
+pub struct Cursor {
+  pub created_date: DateTime<Utc>,
+  pub id: i32,
+}
+
+impl Cursor {
+  pub fn encode(&self) -> String {
+    let raw = format!("{}:{}", self.created_date.timestamp_micros(), self.id);
+    raw.bytes().fold(String::with_capacity(raw.len() * 2), |mut hex, byte| {
+      let _ = write!(hex, "{byte:02x}");
+      hex
+    })
+  }
+
+  pub fn decode(cursor: &str) -> Option<Self> {
+    let bytes = (0..cursor.len()).step_by(2)
+      .map(|i| cursor.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+      .collect::<Option<Vec<u8>>>()?;
+    let raw = String::from_utf8(bytes).ok()?;
+
+    let (micros, id) = raw.split_once(':')?;
+    Some(Self {
+      created_date: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+      id: id.parse().ok()?,
+    })
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimelineParam {
+  pub cursor: Option<String>,
+  pub limit: Option<i64>
+}
+
+#[derive(Serialize, Debug)]
+pub struct TimelineItem {
+  #[serde(flatten)]
+  pub entry: Entry,
+  pub feed_id: i32,
+  pub feed_name: String,
+  pub feed_url: String,
+  pub category: String,
+}
+
+impl From<TimelineEntry> for TimelineItem {
+  fn from(timeline_entry: TimelineEntry) -> Self {
+    Self {
+      feed_id: timeline_entry.entry.feed_id,
+      feed_name: timeline_entry.feed_name,
+      feed_url: timeline_entry.feed_url,
+      category: timeline_entry.category,
+      entry: Entry::from(timeline_entry.entry),
+    }
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Timeline {
+  pub entries: Vec<TimelineItem>,
+  // Absent on the last page
+  pub next_cursor: Option<String>,
+}
+
+// The page of entries after the cursor, and the cursor for the page following it
+pub async fn timeline_page(
+  db: PgPool,
+  feeds: &FeedFilter,
+  filter: &EntryFilter,
+  params: &TimelineParam
+) -> Result<(Vec<TimelineEntry>, Option<String>), Response> {
+  let before = match params.cursor.as_deref().map(Cursor::decode) {
+    Some(None) => return Err((StatusCode::BAD_REQUEST, "Invalid cursor".to_string()).into_response()),
+    Some(Some(cursor)) => Some((cursor.created_date, cursor.id)),
+    None => None,
+  };
+  let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+  // One extra entry tells whether there's another page
+  let entry_db = EntryDataSource::new(db);
+  let mut entries = entry_db.get_timeline(feeds, filter, before, limit + 1).await
+    .map_err(|e| e.into_response())?;
+
+  let next_cursor = if entries.len() as i64 > limit {
+    entries.truncate(limit as usize);
+    entries.last().map(|last| Cursor { created_date: last.entry.created_date, id: last.entry.id }.encode())
+  } else {
+    None
+  };
+
+  Ok((entries, next_cursor))
+}
+
+// Takes the same filters as /feeds, read from the same query string
+pub async fn get_timeline(
+  State(state): State<AppState>,
+  Query(filters): Query<FeedsParam>,
+  Query(params): Query<TimelineParam>
+) -> Result<impl IntoResponse, impl IntoResponse> {
+  println!("Fetching timeline");
+
+  let (entries, next_cursor) = timeline_page(
+    state.db,
+    &filters.feed_filter(),
+    &filters.entry_filter(None, &SystemClock),
+    &params
+  ).await?;
+
+  Ok::<_, Response>(Json(Timeline {
+    entries: entries.into_iter().map(TimelineItem::from).collect(),
+    next_cursor,
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  #[test]
+  fn test_cursor_round_trip() {
+    let cursor = Cursor {
+      created_date: Utc.with_ymd_and_hms(2024, 10, 9, 18, 55, 25).unwrap() + chrono::Duration::microseconds(123_456),
+      id: 42,
+    };
+
+    let encoded = cursor.encode();
+    assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(Cursor::decode(&encoded), Some(cursor));
+  }
+
+  #[test]
+  fn test_cursor_invalid() {
+    assert_eq!(Cursor::decode(""), None);
+    assert_eq!(Cursor::decode("abc"), None);
+    assert_eq!(Cursor::decode("zz"), None);
+    // Hex for "12345", which has no id
+    assert_eq!(Cursor::decode("3132333435"), None);
+  }
+}
@@ -0,0 +1,215 @@
+use chrono::{DateTime, Months, NaiveDate, NaiveDateTime, Utc};
+use serde::{de, Deserialize, Deserializer};
+
+// Source of the current time, so windows ending "now" can be tested against a fixed instant
+pub trait Clock {
+  fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> {
+    Utc::now()
+  }
+}
+
+#[cfg(test)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+  fn now(&self) -> DateTime<Utc> {
+    self.0
+  }
+}
+
+// A calendar length of time. Months and years are kept apart from days since their length depends
+// on where they land, so P1M back from March 31st is February's last day rather than 30 days.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Duration {
+  months: u32,
+  days: u32,
+  seconds: u32,
+}
+
+impl Duration {
+  pub const DAY: Duration = Duration { months: 0, days: 1, seconds: 0 };
+  pub const WEEK: Duration = Duration { months: 0, days: 7, seconds: 0 };
+  pub const MONTH: Duration = Duration { months: 1, days: 0, seconds: 0 };
+  pub const YEAR: Duration = Duration { months: 12, days: 0, seconds: 0 };
+
+  // The named windows /feeds has always taken, or an ISO 8601 duration like P3D or PT6H
+  pub fn parse(duration: &str) -> Option<Self> {
+    let duration = duration.trim();
+    match duration.to_ascii_lowercase().as_str() {
+      "day" => return Some(Self::DAY),
+      "week" => return Some(Self::WEEK),
+      "month" => return Some(Self::MONTH),
+      "year" => return Some(Self::YEAR),
+      _ => (),
+    }
+
+    let duration = duration.to_ascii_uppercase();
+    let rest = duration.strip_prefix('P')?;
+    let (date_part, time_part) = match rest.split_once('T') {
+      Some((_, "")) => return None,
+      Some((date_part, time_part)) => (date_part, Some(time_part)),
+      None => (rest, None),
+    };
+
+    let mut parsed = Self::default();
+    let mut any = false;
+    for (value, unit) in components(date_part)? {
+      match unit {
+        'Y' => parsed.months = parsed.months.checked_add(value.checked_mul(12)?)?,
+        'M' => parsed.months = parsed.months.checked_add(value)?,
+        'W' => parsed.days = parsed.days.checked_add(value.checked_mul(7)?)?,
+        'D' => parsed.days = parsed.days.checked_add(value)?,
+        _ => return None,
+      }
+      any = true;
+    }
+    for (value, unit) in components(time_part.unwrap_or_default())? {
+      match unit {
+        'H' => parsed.seconds = parsed.seconds.checked_add(value.checked_mul(60 * 60)?)?,
+        'M' => parsed.seconds = parsed.seconds.checked_add(value.checked_mul(60)?)?,
+        'S' => parsed.seconds = parsed.seconds.checked_add(value)?,
+        _ => return None,
+      }
+      any = true;
+    }
+
+    any.then_some(parsed)
+  }
+
+  // Start of the window that ends at end
+  pub fn before(&self, end: DateTime<Utc>) -> DateTime<Utc> {
+    end.checked_sub_months(Months::new(self.months))
+      .and_then(|start| start.checked_sub_signed(chrono::Duration::days(self.days.into())))
+      .and_then(|start| start.checked_sub_signed(chrono::Duration::seconds(self.seconds.into())))
+      .unwrap_or(DateTime::<Utc>::MIN_UTC)
+  }
+
+  // Start of the window that ends now
+  pub fn since(&self, clock: &impl Clock) -> DateTime<Utc> {
+    self.before(clock.now())
+  }
+}
+
+// Number and unit pairs, like [(3, 'D')] for "3D"
+fn components(part: &str) -> Option<Vec<(u32, char)>> {
+  let mut components = Vec::new();
+  let mut digits = String::new();
+  for c in part.chars() {
+    if c.is_ascii_digit() {
+      digits.push(c);
+    } else {
+      components.push((digits.parse().ok()?, c));
+      digits.clear();
+    }
+  }
+  digits.is_empty().then_some(components)
+}
+
+impl<'de> Deserialize<'de> for Duration {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let duration = String::deserialize(deserializer)?;
+    Duration::parse(&duration).ok_or_else(|| de::Error::custom(format!(
+      "invalid duration {duration:?}, expected day, week, month, year or an ISO 8601 duration like P3D"
+    )))
+  }
+}
+
+// RFC 3339 in any offset, read as the instant it names. A query string's unescaped + arrives as a
+// space, dates alone are midnight UTC and times without an offset are taken as UTC.
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+  let timestamp = timestamp.trim();
+  let repaired = match timestamp.rsplit_once(' ') {
+    Some((date_time, offset)) if date_time.contains('T') => format!("{date_time}+{offset}"),
+    _ => timestamp.to_string(),
+  };
+
+  DateTime::parse_from_rfc3339(&repaired).ok()
+    .map(|date_time| date_time.with_timezone(&Utc))
+    .or_else(|| NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f").ok().map(|date_time| date_time.and_utc()))
+    .or_else(|| NaiveDate::parse_from_str(timestamp, "%Y-%m-%d").ok()
+      .and_then(|date| date.and_hms_opt(0, 0, 0))
+      .map(|date_time| date_time.and_utc()))
+}
+
+// For optional timestamp fields in query strings
+pub fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  match Option::<String>::deserialize(deserializer)? {
+    Some(timestamp) => parse_timestamp(&timestamp)
+      .map(Some)
+      .ok_or_else(|| de::Error::custom(format!("invalid timestamp {timestamp:?}, expected RFC 3339"))),
+    None => Ok(None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+  }
+
+  #[test]
+  fn test_parse_aliases() {
+    assert_eq!(Duration::parse("day"), Some(Duration::DAY));
+    assert_eq!(Duration::parse("WEEK"), Some(Duration::WEEK));
+    assert_eq!(Duration::parse("month"), Some(Duration::MONTH));
+    assert_eq!(Duration::parse("year"), Some(Duration::YEAR));
+    assert_eq!(Duration::parse("P1W"), Some(Duration::WEEK));
+    assert_eq!(Duration::parse("P1Y"), Some(Duration::YEAR));
+  }
+
+  #[test]
+  fn test_parse_iso_8601() {
+    let clock = FixedClock(utc(2024, 10, 9, 18, 0));
+    assert_eq!(Duration::parse("P3D").unwrap().since(&clock), utc(2024, 10, 6, 18, 0));
+    assert_eq!(Duration::parse("PT6H").unwrap().since(&clock), utc(2024, 10, 9, 12, 0));
+    assert_eq!(Duration::parse("p1dt30m").unwrap().since(&clock), utc(2024, 10, 8, 17, 30));
+    assert_eq!(Duration::parse("P1Y2M3DT4H5M6S").unwrap().since(&clock), utc(2023, 8, 6, 13, 55) - chrono::Duration::seconds(6));
+  }
+
+  #[test]
+  fn test_parse_invalid() {
+    for duration in ["", "P", "PT", "P3", "3D", "P1H", "PT1D", "P-1D", "P1.5D", "fortnight", "P99999999999D"] {
+      assert_eq!(Duration::parse(duration), None, "{duration}");
+    }
+  }
+
+  #[test]
+  fn test_calendar_months() {
+    // A month back from the 31st lands on the last day of a shorter month
+    let clock = FixedClock(utc(2024, 3, 31, 12, 0));
+    assert_eq!(Duration::MONTH.since(&clock), utc(2024, 2, 29, 12, 0));
+    assert_eq!(Duration::YEAR.since(&FixedClock(utc(2024, 2, 29, 0, 0))), utc(2023, 2, 28, 0, 0));
+    // Unlike the old 4 and 52 week approximations
+    assert_ne!(Duration::MONTH.since(&clock), clock.0 - chrono::Duration::weeks(4));
+  }
+
+  #[test]
+  fn test_parse_timestamp() {
+    let expected = utc(2024, 10, 9, 16, 30);
+    assert_eq!(parse_timestamp("2024-10-09T16:30:00Z"), Some(expected));
+    assert_eq!(parse_timestamp("2024-10-09T18:30:00+02:00"), Some(expected));
+    assert_eq!(parse_timestamp("2024-10-09T12:30:00-04:00"), Some(expected));
+    // + decoded to a space by the query string
+    assert_eq!(parse_timestamp("2024-10-09T18:30:00 02:00"), Some(expected));
+    assert_eq!(parse_timestamp("2024-10-09T16:30:00"), Some(expected));
+    assert_eq!(parse_timestamp("2024-10-09"), Some(utc(2024, 10, 9, 0, 0)));
+    assert_eq!(parse_timestamp("yesterday"), None);
+  }
+}
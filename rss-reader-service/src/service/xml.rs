@@ -1,21 +1,49 @@
-use std::io;
+use std::{fmt, io, time::{Duration, Instant}};
 
 use axum::response::{Response, IntoResponse};
-use quickxml_to_serde::{xml_string_to_json, Config};
-use reqwest::StatusCode;
+use reqwest::{header, Client, StatusCode};
+use serde::Serialize;
 use sqlx::PgPool;
 
-use crate::db::{CacheDataSource, CacheInput};
+use crate::db::{CacheDataSource, CacheInput, CacheValue, EntryDataSource, FeedDataSource};
 
-use super::{fetch_cached, Duration, Feed};
+use super::{charset::decode_body, feed_from_atom, feed_from_json_feed, feed_from_rdf, feed_from_rss, fetch_cached, parser::root_name, ParsedFeed};
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum FetchXmlError {
   Network(reqwest::Error),
+  Http(StatusCode),
   Io(io::Error),
   Parse(String),
-  Cache(String)
+  Cache(String),
+  Database(String)
+}
+
+impl FetchXmlError {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      FetchXmlError::Network(_) => "Network",
+      FetchXmlError::Http(_) => "Http",
+      FetchXmlError::Io(_) => "Io",
+      FetchXmlError::Parse(_) => "Parse",
+      FetchXmlError::Cache(_) => "Cache",
+      FetchXmlError::Database(_) => "Database",
+    }
+  }
+}
+
+impl fmt::Display for FetchXmlError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FetchXmlError::Network(err) => write!(f, "Network error: {}", err),
+      FetchXmlError::Http(status) => write!(f, "HTTP error: {}", status),
+      FetchXmlError::Io(err) => write!(f, "IO error: {}", err),
+      FetchXmlError::Parse(msg) => write!(f, "Parse error: {}", msg),
+      FetchXmlError::Cache(msg) => write!(f, "Cache error: {}", msg),
+      FetchXmlError::Database(msg) => write!(f, "Database error: {}", msg),
+    }
+  }
 }
 
 impl From<reqwest::Error> for FetchXmlError {
@@ -34,54 +62,775 @@ impl IntoResponse for FetchXmlError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
           FetchXmlError::Network(_) => (StatusCode::BAD_GATEWAY, "Failed to fetch feed XML."),
+          FetchXmlError::Http(_) => (StatusCode::BAD_GATEWAY, "Feed responded with an error status."),
           FetchXmlError::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error."),
           FetchXmlError::Parse(_) => (StatusCode::BAD_REQUEST, "Failed to parse feed XML."),
-          FetchXmlError::Cache(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Cache error.")
+          FetchXmlError::Cache(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Cache error."),
+          FetchXmlError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error.")
         };
         (status, error_message).into_response()
     }
 }
 
-async fn fetch_feed_xml(route: &str) -> Result<String, FetchXmlError> {
-  let response = reqwest::get(route).await.map_err(FetchXmlError::from)?;
-  let content = response.text().await.map_err(FetchXmlError::from)?;
-  Ok(content)
+#[derive(Debug, PartialEq)]
+enum FetchedXml {
+  NotModified,
+  Modified {
+    xml_string: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct FetchResponse {
+  xml: FetchedXml,
+  status: StatusCode,
+  content_type: Option<String>,
+  // Seconds from Cache-Control: max-age
+  max_age: Option<i64>,
+  // Set when the body didn't decode cleanly in its encoding
+  decode_warning: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct FetchStats {
+  pub status_code: Option<u16>,
+  pub byte_count: Option<usize>,
+  pub latency: Duration,
+}
+
+#[derive(Debug)]
+pub struct IngestResult {
+  pub changed: bool,
+  // Longest of the publisher's polling hints in seconds
+  pub refresh_hint: Option<i64>,
+  // Entries that were skipped or repaired while parsing
+  pub warnings: Vec<String>,
+}
+
+fn max_age(cache_control: &str) -> Option<i64> {
+  let directives: Vec<&str> = cache_control.split(',').map(|d| d.trim()).collect();
+  if directives.iter().any(|d| d.eq_ignore_ascii_case("no-cache") || d.eq_ignore_ascii_case("no-store")) {
+    return None;
+  }
+
+  directives.iter()
+    .find_map(|d| d.strip_prefix("max-age="))
+    .and_then(|secs| secs.trim_matches('"').parse().ok())
+    .filter(|secs: &i64| *secs > 0)
+}
+
+fn header_value(response: &reqwest::Response, name: header::HeaderName) -> Option<String> {
+  response.headers()
+    .get(name)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string())
+}
+
+async fn fetch_feed_xml(client: &Client, route: &str, cached: Option<&CacheValue>) -> Result<FetchResponse, FetchXmlError> {
+  let mut request = client.get(route);
+
+  // Send the validators from the last response so unchanged feeds can answer 304
+  if let Some(cache_value) = cached {
+    if let Some(etag) = &cache_value.etag {
+      request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &cache_value.last_modified {
+      request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+  }
+
+  let response = request.send().await.map_err(FetchXmlError::from)?;
+  let max_age = header_value(&response, header::CACHE_CONTROL).and_then(|value| max_age(&value));
+  let content_type = header_value(&response, header::CONTENT_TYPE);
+  let status = response.status();
+  if cached.is_some() && status == StatusCode::NOT_MODIFIED {
+    return Ok(FetchResponse { xml: FetchedXml::NotModified, status, content_type, max_age, decode_warning: None });
+  }
+  if !status.is_success() {
+    return Err(FetchXmlError::Http(status));
+  }
+
+  let etag = header_value(&response, header::ETAG);
+  let last_modified = header_value(&response, header::LAST_MODIFIED);
+  // Decoded here rather than by reqwest, which only knows about the Content-Type charset
+  let bytes = response.bytes().await.map_err(FetchXmlError::from)?;
+  let decoded = decode_body(&bytes, content_type.as_deref());
+  let xml_string = decoded.text;
+
+  Ok(FetchResponse {
+    xml: FetchedXml::Modified { xml_string, etag, last_modified },
+    status,
+    content_type,
+    max_age,
+    decode_warning: decoded.warning,
+  })
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+  Rss,
+  Rdf,
+  Atom,
+  JsonFeed,
 }
 
-pub async fn fetch_feed_json(
+// Trusts a feed specific Content-Type, then falls back to sniffing the document since plenty of
+// publishers serve feeds as text/xml, text/html or application/octet-stream
+pub fn detect_feed_format(body: &str, content_type: Option<&str>) -> Option<FeedFormat> {
+  let mime = content_type
+    .and_then(|content_type| content_type.split(';').next())
+    .map(|mime| mime.trim().to_ascii_lowercase());
+
+  match mime.as_deref() {
+    Some("application/feed+json") => return Some(FeedFormat::JsonFeed),
+    Some("application/rss+xml") => return Some(FeedFormat::Rss),
+    Some("application/rdf+xml") => return Some(FeedFormat::Rdf),
+    Some("application/atom+xml") => return Some(FeedFormat::Atom),
+    _ => (),
+  }
+
+  if body.trim_start().starts_with('{') {
+    Some(FeedFormat::JsonFeed)
+  } else if body.contains("<rdf:RDF") {
+    Some(FeedFormat::Rdf)
+  } else if body.contains("<rss") {
+    Some(FeedFormat::Rss)
+  } else if body.contains("<feed") {
+    Some(FeedFormat::Atom)
+  } else {
+    None
+  }
+}
+
+pub fn parse_feed_xml(xml_string: &str, content_type: Option<&str>, feed_url: Option<&str>) -> Result<ParsedFeed, FetchXmlError> {
+  match detect_feed_format(xml_string, content_type) {
+    Some(FeedFormat::JsonFeed) => {
+      let value = serde_json::from_str(xml_string)
+        .map_err(|e| FetchXmlError::Parse(e.to_string()))?;
+      feed_from_json_feed(value, feed_url)
+        .map_err(|e| FetchXmlError::Parse(e.to_string()))
+    },
+    Some(_) => {
+      // The root element settles which XML format it is, whatever the Content-Type claimed
+      let root = root_name(xml_string)
+        .map_err(|e| FetchXmlError::Parse(e.to_string()))?;
+
+      match root.as_str() {
+        "rss" => feed_from_rss(xml_string, feed_url),
+        "rdf:RDF" => feed_from_rdf(xml_string, feed_url),
+        "feed" => feed_from_atom(xml_string, feed_url),
+        _ => return Err(FetchXmlError::Parse(format!("Unknown feed root element <{}>", root))),
+      }.map_err(|e| FetchXmlError::Parse(e.to_string()))
+    },
+    None => Err(FetchXmlError::Parse("Unknown feed syntax".to_string()))
+  }
+}
+
+// Runs the fetch and parse steps of ingestion without caching or storing anything, for
+// checking a feed before it's saved
+pub async fn fetch_and_parse_feed(client: &Client, feed_url: &str) -> Result<ParsedFeed, FetchXmlError> {
+  let response = fetch_feed_xml(client, feed_url, None).await?;
+  let xml_string = match response.xml {
+    FetchedXml::Modified { xml_string, .. } => xml_string,
+    FetchedXml::NotModified => return Err(FetchXmlError::Parse("Received 304 without a cached feed".to_string())),
+  };
+
+  let mut parsed = parse_feed_xml(&xml_string, response.content_type.as_deref(), Some(feed_url))?;
+  parsed.warnings.splice(0..0, response.decode_warning);
+  Ok(parsed)
+}
+
+// Fetches, parses and stores a feed, returning the stats of the attempt alongside its result so
+// failures can be logged with whatever was learned before they happened
+pub async fn ingest_feed(
+  client: &Client,
+  feed_id: i32,
   feed_name: &str, 
-  feed_category: &str, 
   feed_url: &str,
-  duration: Duration,
-  max_entries: usize,
   db: PgPool,
-) -> Result<Feed, FetchXmlError> {
-  let xml_string: String = if let Some(cache_value) = fetch_cached(feed_name, &db).await
-    .map_err(|e| FetchXmlError::Cache(e.to_string()))? 
-  {
-    // If cached xml_string exists return cached value
-    println!("Resolving cached feed: {feed_name}");
-    cache_value.xml_string
-  } else {
-    // Else fetch xml_string, cache it, and return new value
-    println!("No cached feed, fetching live: {feed_name}");
-    let new_xml_string = fetch_feed_xml(feed_url).await?;
-    let cache = CacheDataSource::new(&db.to_owned());
-    cache.cache_value(CacheInput { name: feed_name.to_string(), xml_string: new_xml_string.clone() }).await
-      .map_err(|e| FetchXmlError::Cache(e.to_string()))?;
-    new_xml_string
+) -> (FetchStats, Result<IngestResult, FetchXmlError>) {
+  let mut stats = FetchStats::default();
+  let result = fetch_and_store_feed(client, feed_id, feed_name, feed_url, db, &mut stats).await;
+  (stats, result)
+}
+
+async fn fetch_and_store_feed(
+  client: &Client,
+  feed_id: i32,
+  feed_name: &str, 
+  feed_url: &str,
+  db: PgPool,
+  stats: &mut FetchStats,
+) -> Result<IngestResult, FetchXmlError> {
+  let cached = fetch_cached(feed_name, &db).await
+    .map_err(|e| FetchXmlError::Cache(e.to_string()))?;
+
+  // Revalidate (or fetch) xml_string with the cached validators, cache it, and store its entries
+  let cache = CacheDataSource::new(&db.to_owned());
+  let started = Instant::now();
+  let response = fetch_feed_xml(client, feed_url, cached.as_ref()).await;
+  stats.latency = started.elapsed();
+  if let Err(FetchXmlError::Http(status)) = &response {
+    stats.status_code = Some(status.as_u16());
+  }
+  let response = response?;
+
+  stats.status_code = Some(response.status.as_u16());
+  stats.byte_count = Some(match &response.xml {
+    FetchedXml::Modified { xml_string, .. } => xml_string.len(),
+    FetchedXml::NotModified => 0,
+  });
+  let (xml_string, changed) = match (response.xml, cached) {
+    (FetchedXml::NotModified, Some(cache_value)) => {
+      cache.refresh_cached_value(feed_name.to_string()).await
+        .map_err(|e| FetchXmlError::Cache(e.to_string()))?;
+      (cache_value.xml_string, false)
+    },
+    (FetchedXml::Modified { xml_string, etag, last_modified }, cached) => {
+      let changed = cached.is_none_or(|cache_value| cache_value.xml_string != xml_string);
+      cache.cache_value(CacheInput {
+        name: feed_name.to_string(),
+        xml_string: xml_string.clone(),
+        etag,
+        last_modified
+      }).await
+        .map_err(|e| FetchXmlError::Cache(e.to_string()))?;
+      (xml_string, changed)
+    },
+    (FetchedXml::NotModified, None) => {
+      return Err(FetchXmlError::Parse("Received 304 without a cached feed".to_string()));
+    }
   };
 
-  let value = xml_string_to_json(xml_string.clone(), &Config::new_with_defaults())
-    .map_err(|e| FetchXmlError::Parse(e.to_string()))?;
+  let mut parsed = parse_feed_xml(&xml_string, response.content_type.as_deref(), Some(feed_url))?;
+  parsed.warnings.splice(0..0, response.decode_warning);
+  for warning in &parsed.warnings {
+    eprintln!("Warning while parsing feed {}: {}", feed_name, warning);
+  }
 
-  if xml_string.contains("<rss") {
-    Feed::try_from_rss(feed_name.to_string(), feed_category.to_string(), duration, max_entries, value)
-      .map_err(|e| FetchXmlError::Parse(e.to_string()))
-  } else if xml_string.contains("<feed") {
-    Feed::try_from_atom(feed_name.to_string(), feed_category.to_string(), duration, max_entries, value)
-      .map_err(|e| FetchXmlError::Parse(e.to_string()))
-  } else {
-    Err(FetchXmlError::Parse("Unknown feed syntax".to_string()))
+  let feed_db = FeedDataSource::new(db.clone());
+  feed_db.update_metadata(feed_id, &parsed.metadata()).await
+    .map_err(|(_, e)| FetchXmlError::Database(e))?;
+
+  let entry_db = EntryDataSource::new(db);
+  entry_db.upsert_entries(feed_id, parsed.entries).await
+    .map_err(|(_, e)| FetchXmlError::Database(e))?;
+
+  Ok(IngestResult {
+    changed,
+    refresh_hint: response.max_age.max(parsed.refresh_hint),
+    warnings: parsed.warnings,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Utc;
+  use wiremock::{matchers::{header, header_regex, method, path}, Mock, MockServer, ResponseTemplate};
+
+  use super::*;
+
+  const XML: &str = "<rss><channel><item><title>Hello</title></item></channel></rss>";
+  const FEED_URL: &str = "https://example.org/feed.xml";
+
+  fn cached_value(etag: Option<&str>, last_modified: Option<&str>) -> CacheValue {
+    CacheValue {
+      name: "Test Feed".to_string(),
+      xml_string: XML.to_string(),
+      etag: etag.map(|s| s.to_string()),
+      last_modified: last_modified.map(|s| s.to_string()),
+      created_date: Utc::now(),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_stores_validators() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("ETag", "\"abc123\"")
+        .insert_header("Last-Modified", "Wed, 09 Oct 2024 18:55:25 GMT")
+        .set_body_string(XML))
+      .mount(&server)
+      .await;
+
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), None).await.unwrap();
+    assert_eq!(result.xml, FetchedXml::Modified {
+      xml_string: XML.to_string(),
+      etag: Some("\"abc123\"".to_string()),
+      last_modified: Some("Wed, 09 Oct 2024 18:55:25 GMT".to_string()),
+    });
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_not_modified() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .and(header("If-None-Match", "\"abc123\""))
+      .and(header_regex("If-Modified-Since", "^Wed, 09 Oct 2024 18:55:25 GMT$"))
+      .respond_with(ResponseTemplate::new(304))
+      .expect(1)
+      .mount(&server)
+      .await;
+
+    let cached = cached_value(Some("\"abc123\""), Some("Wed, 09 Oct 2024 18:55:25 GMT"));
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), Some(&cached)).await.unwrap();
+    assert_eq!(result.xml, FetchedXml::NotModified);
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_modified_since_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .and(header("If-None-Match", "\"old\""))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("ETag", "\"new\"")
+        .set_body_string(XML))
+      .mount(&server)
+      .await;
+
+    let cached = cached_value(Some("\"old\""), None);
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), Some(&cached)).await.unwrap();
+    assert_eq!(result.xml, FetchedXml::Modified {
+      xml_string: XML.to_string(),
+      etag: Some("\"new\"".to_string()),
+      last_modified: None,
+    });
   }
-}
\ No newline at end of file
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_without_validators() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200).set_body_string(XML))
+      .mount(&server)
+      .await;
+
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), None).await.unwrap();
+    assert_eq!(result.xml, FetchedXml::Modified {
+      xml_string: XML.to_string(),
+      etag: None,
+      last_modified: None,
+    });
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_max_age() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("Cache-Control", "public, max-age=1800")
+        .set_body_string(XML))
+      .mount(&server)
+      .await;
+
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), None).await.unwrap();
+    assert_eq!(result.max_age, Some(1800));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_transcodes() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("Content-Type", "application/rss+xml")
+        .set_body_bytes(include_bytes!("../../fixtures/charset/windows-1252.xml").to_vec()))
+      .mount(&server)
+      .await;
+
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), None).await.unwrap();
+    let FetchedXml::Modified { xml_string, .. } = result.xml else {
+      panic!("Expected a modified response");
+    };
+    let parsed = parse_feed_xml(&xml_string, result.content_type.as_deref(), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries[0].title, "Café – “Ünïcödé” €5");
+    assert_eq!(result.decode_warning, None);
+  }
+
+  #[test]
+  fn test_max_age() {
+    assert_eq!(max_age("max-age=600"), Some(600));
+    assert_eq!(max_age("public, max-age=3600, must-revalidate"), Some(3600));
+    assert_eq!(max_age("no-cache, max-age=3600"), None);
+    assert_eq!(max_age("max-age=0"), None);
+    assert_eq!(max_age("private"), None);
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_error_status() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(404).set_body_string("<html>Not Found</html>"))
+      .mount(&server)
+      .await;
+
+    let result = fetch_feed_xml(&Client::new(), &format!("{}/feed.xml", server.uri()), None).await;
+    assert!(matches!(result, Err(FetchXmlError::Http(StatusCode::NOT_FOUND))));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_feed_xml_times_out() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200).set_body_string(XML).set_delay(Duration::from_secs(5)))
+      .mount(&server)
+      .await;
+
+    let client = Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+    let started = Instant::now();
+    let result = fetch_feed_xml(&client, &format!("{}/feed.xml", server.uri()), None).await;
+    assert!(matches!(result, Err(FetchXmlError::Network(e)) if e.is_timeout()));
+    assert!(started.elapsed() < Duration::from_secs(5));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_and_parse_feed() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/feed.xml"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("Content-Type", "text/xml")
+        .set_body_string("<feed xmlns=\"http://www.w3.org/2005/Atom\"><title type=\"html\">Fish &amp;amp; <b>Chips</b></title>\
+          <entry><id>1</id><title>Hello</title><link href=\"/hello\"/><updated>2024-10-09T18:55:25Z</updated></entry></feed>"))
+      .mount(&server)
+      .await;
+
+    let feed_url = format!("{}/feed.xml", server.uri());
+    let parsed = fetch_and_parse_feed(&Client::new(), &feed_url).await.unwrap();
+    assert_eq!(parsed.format, FeedFormat::Atom);
+    assert_eq!(parsed.title.as_deref(), Some("Fish & Chips"));
+    assert_eq!(parsed.entries.len(), 1);
+    assert_eq!(parsed.entries[0].url, format!("{}/hello", server.uri()));
+  }
+
+  #[tokio::test]
+  async fn test_fetch_and_parse_feed_not_a_feed() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+      .and(path("/"))
+      .respond_with(ResponseTemplate::new(200)
+        .insert_header("Content-Type", "text/html")
+        .set_body_string("<html><body>Hello</body></html>"))
+      .mount(&server)
+      .await;
+
+    let result = fetch_and_parse_feed(&Client::new(), &format!("{}/", server.uri())).await;
+    assert!(matches!(result, Err(FetchXmlError::Parse(_))));
+  }
+
+  #[test]
+  fn test_detect_feed_format_content_type() {
+    assert_eq!(detect_feed_format("{}", Some("application/feed+json; charset=utf-8")), Some(FeedFormat::JsonFeed));
+    assert_eq!(detect_feed_format(XML, Some("application/rss+xml")), Some(FeedFormat::Rss));
+    assert_eq!(detect_feed_format("<feed></feed>", Some("Application/Atom+XML")), Some(FeedFormat::Atom));
+  }
+
+  #[test]
+  fn test_detect_feed_format_sniffed() {
+    assert_eq!(detect_feed_format("  {\"version\": \"https://jsonfeed.org/version/1.1\"}", Some("application/json")), Some(FeedFormat::JsonFeed));
+    assert_eq!(detect_feed_format(XML, Some("text/xml")), Some(FeedFormat::Rss));
+    assert_eq!(detect_feed_format("<?xml version=\"1.0\"?><feed></feed>", None), Some(FeedFormat::Atom));
+    assert_eq!(detect_feed_format("<rdf:RDF xmlns=\"http://purl.org/rss/1.0/\"></rdf:RDF>", Some("text/xml")), Some(FeedFormat::Rdf));
+    assert_eq!(detect_feed_format("<html></html>", Some("text/html")), None);
+  }
+
+  #[test]
+  fn test_parse_feed_title_and_format() {
+    let rss = "<rss><channel><title>  Fish &amp;amp; <b>Chips</b> </title><item><title>Hello</title></item></channel></rss>";
+    let parsed = parse_feed_xml(rss, Some("application/rss+xml"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.format, FeedFormat::Rss);
+    assert_eq!(parsed.title.as_deref(), Some("Fish & Chips"));
+
+    // The root element wins over a mislabelled Content-Type
+    let rdf = "<rdf:RDF xmlns=\"http://purl.org/rss/1.0/\"><channel><title>Slashdot</title></channel></rdf:RDF>";
+    let parsed = parse_feed_xml(rdf, Some("application/rss+xml"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.format, FeedFormat::Rdf);
+    assert_eq!(parsed.title.as_deref(), Some("Slashdot"));
+
+    let parsed = parse_feed_xml(XML, None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.title, None);
+  }
+
+  #[test]
+  fn test_parse_feed_metadata() {
+    let metadata = |title: &str, site_url: &str, description: &str, icon_url: &str| crate::db::FeedMetadata {
+      title: Some(title.to_string()),
+      site_url: Some(site_url.to_string()),
+      description: Some(description.to_string()),
+      icon_url: Some(icon_url.to_string()),
+    };
+
+    let rss = "<rss xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\"><channel><title>Fish</title><link>/blog/</link>\
+      <description>Fresh &lt;em&gt;every&lt;/em&gt; day</description><itunes:image href=\"cover.jpg\"/></channel></rss>";
+    let parsed = parse_feed_xml(rss, None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.metadata(), metadata("Fish", "https://example.org/blog/", "Fresh every day", "https://example.org/blog/cover.jpg"));
+
+    let atom = "<feed xmlns=\"http://www.w3.org/2005/Atom\"><title>Fish</title><subtitle type=\"html\">Fresh &amp;amp; fried</subtitle>\
+      <link rel=\"alternate\" href=\"https://fish.example.org/\"/><logo>/logo.png</logo><icon>/favicon.ico</icon></feed>";
+    let parsed = parse_feed_xml(atom, None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.metadata(), metadata("Fish", "https://fish.example.org/", "Fresh & fried", "https://fish.example.org/favicon.ico"));
+
+    let rdf = "<rdf:RDF xmlns=\"http://purl.org/rss/1.0/\"><channel><title>Fish</title><link>https://fish.example.org/</link>\
+      <description>Fresh</description></channel><image><url>https://fish.example.org/logo.png</url></image></rdf:RDF>";
+    let parsed = parse_feed_xml(rdf, None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.metadata(), metadata("Fish", "https://fish.example.org/", "Fresh", "https://fish.example.org/logo.png"));
+
+    // Plain text in JSON Feed, so markup-like text is kept as written
+    let json = r#"{"version": "https://jsonfeed.org/version/1.1", "title": "Fish", "home_page_url": "https://fish.example.org/",
+      "description": "Fish <3 chips", "favicon": "/favicon.ico", "items": []}"#;
+    let parsed = parse_feed_xml(json, Some("application/feed+json"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.metadata(), metadata("Fish", "https://fish.example.org/", "Fish <3 chips", "https://fish.example.org/favicon.ico"));
+
+    // Links a browser can't open are dropped
+    let rss = "<rss><channel><title>Fish</title><link>javascript:void(0)</link><image><url>data:image/png;base64,AAAA</url></image></channel></rss>";
+    let parsed = parse_feed_xml(rss, None, Some(FEED_URL)).unwrap();
+    assert_eq!((parsed.site_url, parsed.icon_url), (None, None));
+  }
+
+  // Script and data links fall back to a permalink id, or leave the entry out when there's none
+  #[test]
+  fn test_parse_rss_unsafe_links() {
+    let parsed = parse_feed_xml(include_str!("../../fixtures/links/javascript.rss"), None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 1);
+    let entry = &parsed.entries[0];
+    assert_eq!(entry.url, "https://example.org/posts/1");
+    assert_eq!(entry.comments_url, None);
+    assert_eq!(entry.enclosures.iter().map(|enclosure| enclosure.url.as_str()).collect::<Vec<_>>(), vec!["https://example.org/episodes/1.mp3"]);
+    assert!(entry.thumbnails.is_empty());
+    assert_eq!(parsed.warnings, vec!["Skipped entry post-2: missing link"]);
+  }
+
+  #[test]
+  fn test_parse_atom_unsafe_links() {
+    let parsed = parse_feed_xml(include_str!("../../fixtures/links/javascript.atom"), None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 1);
+    assert_eq!(parsed.entries[0].url, "https://example.org/posts/1");
+    assert_eq!(parsed.entries[0].comments_url, None);
+    assert_eq!(parsed.warnings, vec!["Skipped entry tag:example.org,2024:2: missing link"]);
+  }
+
+  #[test]
+  fn test_parse_json_feed_unsafe_links() {
+    let parsed = parse_feed_xml(include_str!("../../fixtures/links/javascript.json"), Some("application/feed+json"), Some(FEED_URL)).unwrap();
+    let urls: Vec<&str> = parsed.entries.iter().map(|entry| entry.url.as_str()).collect();
+    assert_eq!(urls, vec!["https://example.org/posts/1", "https://elsewhere.example.org/2"]);
+    assert_eq!(parsed.entries[0].image_url, None);
+    assert_eq!(parsed.entries[0].enclosures.iter().map(|enclosure| enclosure.url.as_str()).collect::<Vec<_>>(), vec!["https://example.org/episodes/1.mp3"]);
+    assert_eq!(parsed.warnings, vec!["Skipped entry 3: missing link"]);
+  }
+
+  #[test]
+  fn test_parse_json_feed() {
+    let body = r#"{
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [{ "id": "1", "url": "https://example.org/1", "title": "Hello", "date_published": "2024-10-09T18:55:25Z" }]
+    }"#;
+
+    let parsed = parse_feed_xml(body, Some("application/feed+json"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 1);
+    assert_eq!(parsed.entries[0].identity, "1");
+    assert_eq!(parsed.entries[0].title, "Hello");
+    assert!(parsed.warnings.is_empty());
+  }
+
+  #[test]
+  fn test_parse_json_feed_details() {
+    let body = r#"{
+      "version": "https://jsonfeed.org/version/1.1",
+      "authors": [{ "name": "Ignored" }],
+      "items": [{
+        "id": "1",
+        "url": "https://example.org/1",
+        "summary": "A short summary",
+        "content_text": "Fish & chips",
+        "date_modified": "2024-10-10T08:00:00Z",
+        "authors": [{ "name": "Pepper" }, { "url": "https://example.org/nellie" }],
+        "tags": ["food", "uk"]
+      }]
+    }"#;
+
+    let entry = &parse_feed_xml(body, Some("application/feed+json"), Some(FEED_URL)).unwrap().entries[0];
+    assert_eq!(entry.title, "A short summary");
+    assert_eq!(entry.summary.as_deref(), Some("A short summary"));
+    assert_eq!(entry.content_html.as_deref(), Some("Fish &amp; chips"));
+    assert_eq!(entry.authors, vec!["Pepper".to_string(), "https://example.org/nellie".to_string()]);
+    assert_eq!(entry.categories, vec!["food".to_string(), "uk".to_string()]);
+    assert_eq!(entry.updated_date.unwrap().to_string(), "2024-10-10 08:00:00 UTC");
+  }
+
+  #[test]
+  fn test_parse_rss_details() {
+    let body = r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+      <channel>
+        <item>
+          <guid>twir-568</guid>
+          <title>This Week in Rust 568</title>
+          <link>https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/</link>
+          <description>&lt;p&gt;Hello&lt;/p&gt;</description>
+          <atom:updated>2024-10-10T08:00:00Z</atom:updated>
+          <category>Rust</category>
+          <comments>https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/#comments</comments>
+        </item>
+      </channel>
+    </rss>"#;
+
+    let entry = &parse_feed_xml(body, Some("application/rss+xml"), Some(FEED_URL)).unwrap().entries[0];
+    assert_eq!(entry.identity, "twir-568");
+    assert_eq!(entry.title, "This Week in Rust 568");
+    assert_eq!(entry.summary.as_deref(), Some("<p>Hello</p>"));
+    assert_eq!(entry.categories, vec!["Rust".to_string()]);
+    assert_eq!(entry.updated_date.unwrap().to_string(), "2024-10-10 08:00:00 UTC");
+    assert_eq!(entry.comments_url.as_deref(), Some("https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/#comments"));
+  }
+
+  #[test]
+  fn test_parse_feed_sanitizes_entries() {
+    let body = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+        <entry>
+          <link href="https://example.org/posts/1" />
+          <title type="html">I Made a &lt;em&gt;Terrible&lt;/em&gt; Video Game&lt;script&gt;alert(1)&lt;/script&gt;</title>
+          <summary type="html">&lt;p onclick="alert(1)"&gt;See &lt;a href="/games"&gt;the games&lt;/a&gt;&lt;/p&gt;</summary>
+          <content type="html">&lt;img src="https://stats.wordpress.com/b.gif?v=1" /&gt;&lt;p&gt;Body&lt;/p&gt;</content>
+        </entry>
+      </feed>"#;
+
+    let entry = &parse_feed_xml(body, Some("application/atom+xml"), Some(FEED_URL)).unwrap().entries[0];
+    assert_eq!(entry.title, "I Made a Terrible Video Game");
+    assert_eq!(entry.summary.as_deref(), Some(r#"<p>See <a href="https://example.org/games" rel="noopener noreferrer">the games</a></p>"#));
+    assert_eq!(entry.content_html.as_deref(), Some("<p>Body</p>"));
+    assert_eq!(entry.preview.as_deref(), Some("See the games"));
+  }
+
+  #[test]
+  fn test_parse_feed_resolves_atom_xml_base() {
+    let body = r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:base="https://technicalgrimoire.com/">
+        <link rel="self" href="https://feeds.example.net/grimoire.atom" />
+        <link rel="alternate" type="text/html" href="/david/" />
+        <entry>
+          <link rel="alternate" type="text/html" href="2024/10/keyburg-videogame" />
+          <content type="html">&lt;img src="keyburg.png" alt="Keyburg" /&gt;</content>
+        </entry>
+        <entry xml:base="https://mirror.example.org/grimoire/">
+          <link rel="alternate" href="2024/11/another" />
+          <link rel="enclosure" type="audio/mpeg" href="../audio/another.mp3" />
+        </entry>
+      </feed>"#;
+
+    let parsed = parse_feed_xml(body, Some("application/atom+xml"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries[0].url, "https://technicalgrimoire.com/2024/10/keyburg-videogame");
+    assert_eq!(parsed.entries[0].content_html.as_deref(), Some(r#"<img src="https://technicalgrimoire.com/keyburg.png" alt="Keyburg">"#));
+    assert_eq!(parsed.entries[1].url, "https://mirror.example.org/grimoire/2024/11/another");
+    assert_eq!(parsed.entries[1].enclosures[0].url, "https://mirror.example.org/audio/another.mp3");
+  }
+
+  // A relative xml:base is taken against the feed URL, whatever host the channel links to, and a
+  // link's own xml:base wins over its entry's
+  #[test]
+  fn test_parse_atom_link_xml_base() {
+    let parsed = parse_feed_xml(include_str!("../../fixtures/links/xml-base.atom"), None, Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.site_url.as_deref(), Some("https://www.example.com/journal/"));
+    assert_eq!(parsed.icon_url.as_deref(), Some("https://example.org/archive/icon.png"));
+    assert_eq!(parsed.entries[0].url, "https://example.org/archive/posts/1");
+
+    let entry = &parsed.entries[1];
+    assert_eq!(entry.url, "https://static.example.org/2024/posts/2");
+    assert_eq!(entry.comments_url.as_deref(), Some("https://mirror.example.org/comments/2"));
+    assert_eq!(entry.enclosures[0].url, "https://cdn.example.org/audio/2.mp3");
+  }
+
+  #[test]
+  fn test_parse_feed_resolves_against_channel_link() {
+    let body = r#"<rss version="2.0"><channel>
+        <link>https://this-week-in-rust.org/blog/</link>
+        <item>
+          <title>This Week in Rust 568</title>
+          <link>2024/10/09/this-week-in-rust-568/</link>
+          <comments>/comments/568</comments>
+        </item>
+      </channel></rss>"#;
+
+    let entry = &parse_feed_xml(body, Some("application/rss+xml"), Some(FEED_URL)).unwrap().entries[0];
+    assert_eq!(entry.url, "https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/");
+    assert_eq!(entry.identity, "https://this-week-in-rust.org/blog/2024/10/09/this-week-in-rust-568/");
+    assert_eq!(entry.comments_url.as_deref(), Some("https://this-week-in-rust.org/comments/568"));
+  }
+
+  #[test]
+  fn test_parse_feed_resolves_against_feed_url() {
+    let body = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/">
+        <channel><title>arXiv</title></channel>
+        <item><title>Scaling Laws for Terrible Video Games</title><link>/abs/2410.07095</link></item>
+      </rdf:RDF>"#;
+
+    let entry = &parse_feed_xml(body, Some("application/rdf+xml"), Some(FEED_URL)).unwrap().entries[0];
+    assert_eq!(entry.url, "https://example.org/abs/2410.07095");
+  }
+
+  #[test]
+  fn test_parse_feed_tolerates_bad_entry_dates() {
+    let body = r#"{
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [
+        { "id": "1", "url": "https://example.org/1", "date_published": "last tuesday" },
+        { "id": "2", "url": "https://example.org/2", "date_published": "garbage", "date_modified": "Wed, 9 Oct 2024 14:55:25 EDT" },
+        { "id": "3", "url": "https://example.org/3" }
+      ]
+    }"#;
+
+    let parsed = parse_feed_xml(body, Some("application/feed+json"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 3);
+    assert_eq!(parsed.entries[0].created_date, None);
+    assert_eq!(parsed.entries[1].created_date.unwrap().to_string(), "2024-10-09 18:55:25 UTC");
+    assert_eq!(parsed.entries[2].created_date, None);
+    assert_eq!(parsed.warnings, vec!["Unparseable date for entry 1: last tuesday".to_string()]);
+  }
+
+  #[test]
+  fn test_parse_feed_skips_bad_entries() {
+    let body = r#"{
+      "version": "https://jsonfeed.org/version/1.1",
+      "items": [
+        { "id": "https://example.org/1", "content_text": "  Just a\n  quick note  " },
+        { "id": "2" },
+        { "url": "https://example.org/3" },
+        { "id": "4", "url": "https://example.org/4", "title": "Fine" }
+      ]
+    }"#;
+
+    let parsed = parse_feed_xml(body, Some("application/feed+json"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 2);
+    assert_eq!(parsed.entries[0].url, "https://example.org/1");
+    assert_eq!(parsed.entries[0].title, "Just a quick note");
+    assert_eq!(parsed.entries[1].identity, "4");
+    assert_eq!(parsed.warnings, vec![
+      "Skipped entry at position 3: missing field `id`".to_string(),
+      "Skipped entry 2: missing link".to_string(),
+    ]);
+  }
+
+  #[test]
+  fn test_parse_feed_xml_uses_root_element() {
+    let body = "<rss version=\"2.0\"><channel>
+        <item><title>Hello</title><link>https://example.org/1</link></item>
+      </channel></rss>";
+
+    // Mislabeled as Atom, the document itself says RSS
+    let parsed = parse_feed_xml(body, Some("application/atom+xml"), Some(FEED_URL)).unwrap();
+    assert_eq!(parsed.entries.len(), 1);
+    assert_eq!(parsed.entries[0].url, "https://example.org/1");
+
+    let unknown = parse_feed_xml("<rss-ish><item/></rss-ish>", Some("application/rss+xml"), Some(FEED_URL));
+    assert!(unknown.is_err());
+  }
+}